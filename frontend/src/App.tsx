@@ -400,12 +400,7 @@ function App() {
               version={bubbleVersion}
               token={activeToken}
               provider={provider}
-              initialTo={isNewMailMode ? [] : (selectedMessage?.from ? [
-                (() => {
-                  const match = selectedMessage.from.match(/<([^>]+)>/);
-                  return match ? match[1] : selectedMessage.from.trim();
-                })()
-              ] : [])}
+              initialTo={isNewMailMode ? [] : (selectedMessage?.from ? [selectedMessage.from.address] : [])}
               initialSubject={isNewMailMode ? "Quote Proposal" : (selectedMessage?.subject ? `RE: ${selectedMessage.subject}` : "Quote Proposal")}
               threadId={isNewMailMode ? undefined : selectedMessage?.thread_id}
               onClose={() => {
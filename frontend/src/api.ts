@@ -1,14 +1,37 @@
+export interface EmailAddress {
+    name?: string | null;
+    address: string;
+}
+
 export interface Message {
     id: string;
     thread_id: string;
     snippet: string;
     subject?: string;
-    from?: string;
-    to?: string[];
-    cc?: string[];
-    date?: string;
+    from?: EmailAddress;
+    to?: EmailAddress[];
+    cc?: EmailAddress[];
+    date?: string; // RFC 3339 UTC
     unread: boolean;
     has_attachments: boolean;
+    messages_in_thread?: number;
+    participants?: EmailAddress[];
+    label_ids?: string[];
+    size_estimate?: number;
+    importance?: "low" | "normal" | "high";
+    flagged?: boolean;
+    categories?: string[];
+    raw_date?: string;
+    raw_from?: string;
+}
+
+export interface MessageList {
+    messages: Message[];
+    nextPageToken: string | null;
+    page: number;
+    next_page: number | null;
+    resultSizeEstimate: number | null;
+    warnings?: string[];
 }
 
 export interface UserProfile {
@@ -21,6 +44,11 @@ export interface Label {
     id: string;
     name: string;
     label_type?: string;
+    color?: { text_color: string; background_color: string };
+    parent_id?: string;
+    unread_count?: number;
+    total_count?: number;
+    children?: Label[];
 }
 
 export interface BatchModifyRequest {
@@ -29,6 +57,20 @@ export interface BatchModifyRequest {
     remove_label_ids?: string[];
 }
 
+export interface BatchItemResult {
+    id: string;
+    status: "succeeded" | "failed" | "skipped";
+    error?: string;
+}
+
+export interface BatchModifyResponse {
+    status: "ok" | "partial" | "failed";
+    succeeded: number;
+    failed: number;
+    skipped: number;
+    results: BatchItemResult[];
+}
+
 export interface QuotePreviewParams {
     quote_id: string;
     version?: string;
@@ -51,6 +93,7 @@ export interface SendQuoteRequest {
     pdf_name?: string;
     maildata_identificator?: string;
     company?: string;
+    inline_images?: boolean;
 }
 
 const API_BASE = import.meta.env.PROD ? "" : "http://localhost:3000";
@@ -62,7 +105,8 @@ async function handleResponse(res: Response) {
         let errorMessage = `Error: ${res.status}`;
         try {
             const errorData = await res.json();
-            errorMessage = errorData.error || errorData.details || errorMessage;
+            // Invalid recipients are only named in details
+            errorMessage = (errorData.invalid_addresses && errorData.details) || errorData.error || errorData.details || errorMessage;
         } catch (e) {
             // fallback to status text
         }
@@ -88,7 +132,7 @@ export const api = {
                 ...(globalApiKey ? { "x-api-key": globalApiKey } : {})
             }
         });
-        const data = await handleResponse(res);
+        const data: MessageList = await handleResponse(res);
         return data.messages || [];
     },
 
@@ -102,7 +146,7 @@ export const api = {
         return await handleResponse(res);
     },
 
-    async modifyLabels(token: string, provider: string, req: BatchModifyRequest) {
+    async modifyLabels(token: string, provider: string, req: BatchModifyRequest): Promise<BatchModifyResponse> {
         const res = await fetch(`${API_BASE}/api/labels/batch-modify?provider=${provider}`, {
             method: "POST",
             headers: {
@@ -140,6 +184,18 @@ export const api = {
         return await handleResponse(res);
     },
 
+    async getAttachmentLink(token: string, provider: string, messageId: string, attachmentId: string): Promise<{ url: string, expires_at: number }> {
+        const res = await fetch(`${API_BASE}/api/messages/${encodeURIComponent(messageId)}/attachments/${encodeURIComponent(attachmentId)}/link?provider=${provider}`, {
+            method: "POST",
+            headers: {
+                "Authorization": `Bearer ${token}`,
+                ...(globalApiKey ? { "x-api-key": globalApiKey } : {})
+            }
+        });
+        const data = await handleResponse(res);
+        return { ...data, url: `${API_BASE}${data.url}` };
+    },
+
     async getProfile(token: string, provider: string, company?: string): Promise<UserProfile> {
         let url = `${API_BASE}/api/profile?provider=${provider}`;
         if (company) {
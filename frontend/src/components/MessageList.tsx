@@ -182,7 +182,7 @@ function MessageItem({ message, isSelected, onSelect, labels, onMove, onArchive,
 
             <div className="flex justify-between items-start mb-1">
                 <span className={cn("text-sm truncate max-w-[200px]", message.unread ? "text-foreground" : "")}>
-                    {message.from || "Unknown"}
+                    {message.from?.name || message.from?.address || "Unknown"}
                 </span>
                 <span className="text-xs text-muted-foreground whitespace-nowrap ml-2">
                     {formatDate(message.date)}
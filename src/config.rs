@@ -7,6 +7,7 @@ pub struct Config {
     pub bubble_api_token: String,
     pub widget_api_key: String, // Key exposed in public widget script
     pub allowed_origins: Vec<String>,
+    pub signing_key: String, // Server key for signed attachment links and pagination cursors
 }
 
 impl Config {
@@ -34,11 +35,19 @@ impl Config {
             .filter(|s| !s.is_empty())
             .collect();
 
+        // Falls back to APP_SECRET_KEY so signed links and cursors work without extra setup.
+        // All replicas must share it, otherwise links and page tokens only work on the issuer.
+        let signing_key = std::env::var("URL_SIGNING_KEY")
+            .ok()
+            .filter(|k| !k.is_empty())
+            .unwrap_or_else(|| app_secret_key.clone());
+
         Ok(Self {
             app_secret_key,
             bubble_api_token,
             widget_api_key,
             allowed_origins,
+            signing_key,
         })
     }
 }
@@ -3,7 +3,9 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
+use serde::Serialize;
 use serde_json::json;
+use std::fmt;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -30,6 +32,24 @@ pub enum AppError {
     BadGateway(String),
     #[error("Forbidden: {0}")]
     Forbidden(String),
+    #[error("Not found: {0}")]
+    NotFound(String),
+    #[error("Invalid recipient addresses: {}", .0.iter().map(|e| e.to_string()).collect::<Vec<_>>().join("; "))]
+    InvalidAddresses(Vec<AddressError>),
+}
+
+/// One rejected recipient, reported back to the caller together with all the others
+#[derive(Serialize, Debug)]
+pub struct AddressError {
+    pub field: &'static str,
+    pub input: String,
+    pub error: String,
+}
+
+impl fmt::Display for AddressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {:?}: {}", self.field, self.input, self.error)
+    }
 }
 
 impl IntoResponse for AppError {
@@ -86,14 +106,20 @@ impl IntoResponse for AppError {
             },
             AppError::BadGateway(ref msg) => (StatusCode::BAD_GATEWAY, msg.as_str()),
             AppError::Forbidden(ref msg) => (StatusCode::FORBIDDEN, msg.as_str()),
+            AppError::NotFound(ref msg) => (StatusCode::NOT_FOUND, msg.as_str()),
+            AppError::InvalidAddresses(_) => (StatusCode::BAD_REQUEST, "Invalid recipient addresses"),
             _ => (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error"),
         };
 
-        let body = Json(json!({
+        let mut body = json!({
             "error": error_message,
             "details": self.to_string()
-        }));
+        });
+        // Every rejected recipient, so the client can highlight them all at once
+        if let AppError::InvalidAddresses(ref errors) = self {
+            body["invalid_addresses"] = json!(errors);
+        }
 
-        (status, body).into_response()
+        (status, Json(body)).into_response()
     }
 }
@@ -0,0 +1,299 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AddressError, AppError};
+
+// Recipient handling shared by every send path: `"Name" <addr>` parsing, syntax checks on a
+// practical RFC 5322 subset (dot-atom local part, IDN domains sent as punycode) and
+// case-insensitive deduplication across the recipient fields.
+
+/// RFC 5321 limits
+const MAX_ADDRESS_LENGTH: usize = 254;
+const MAX_LOCAL_PART_LENGTH: usize = 64;
+const MAX_DOMAIN_LABEL_LENGTH: usize = 63;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct EmailAddress {
+    pub name: Option<String>,
+    pub address: String,
+}
+
+impl EmailAddress {
+    // "John Doe <john@example.com>", "<john@example.com>" or a bare "john@example.com"
+    pub fn parse(value: &str) -> Self {
+        let (name, address) = split_mailbox(value);
+        Self::from_parts(name.as_deref(), Some(&address))
+    }
+
+    pub fn from_parts(name: Option<&str>, address: Option<&str>) -> Self {
+        let address = address.unwrap_or_default().trim().to_string();
+        // Graph repeats the address as the name when there is none
+        let name = name
+            .map(str::trim)
+            .filter(|n| !n.is_empty() && !n.eq_ignore_ascii_case(&address))
+            .map(|n| n.to_string());
+        Self { name, address }
+    }
+}
+
+/// `"Name" <address>` (name quoted and escaped) or the bare address
+impl fmt::Display for EmailAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "\"{}\" <{}>", name.replace('\\', "\\\\").replace('"', "\\\""), self.address),
+            None => f.write_str(&self.address),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Recipients {
+    pub to: Vec<EmailAddress>,
+    pub cc: Vec<EmailAddress>,
+    pub bcc: Vec<EmailAddress>,
+    pub reply_to: Vec<EmailAddress>,
+}
+
+/// Parses and validates every recipient, then drops repeats (To wins over Cc, Cc over Bcc;
+/// Reply-To is only deduplicated within itself). Entries may hold several comma/semicolon
+/// separated addresses. All invalid entries are returned at once as `AppError::InvalidAddresses`.
+pub fn normalize_recipients(to: &[String], cc: &[String], bcc: &[String], reply_to: &[String]) -> Result<Recipients, AppError> {
+    let mut errors = Vec::new();
+    let mut seen = HashSet::new();
+
+    let recipients = Recipients {
+        to: parse_list("to", to, &mut seen, &mut errors),
+        cc: parse_list("cc", cc, &mut seen, &mut errors),
+        bcc: parse_list("bcc", bcc, &mut seen, &mut errors),
+        reply_to: parse_list("reply_to", reply_to, &mut HashSet::new(), &mut errors),
+    };
+
+    if !errors.is_empty() {
+        return Err(AppError::InvalidAddresses(errors));
+    }
+    Ok(recipients)
+}
+
+// Valid addresses not in `seen` yet; invalid entries go to `errors`
+fn parse_list(field: &'static str, entries: &[String], seen: &mut HashSet<String>, errors: &mut Vec<AddressError>) -> Vec<EmailAddress> {
+    let mut result = Vec::new();
+    for input in entries.iter().flat_map(|e| split_address_list(e)) {
+        match validate_mailbox(&input) {
+            Ok(address) => {
+                if seen.insert(address.address.to_lowercase()) {
+                    result.push(address);
+                }
+            }
+            Err(error) => errors.push(AddressError { field, input, error }),
+        }
+    }
+    result
+}
+
+/// Splits a header or user input on ',' and ';' outside quoted names and angle brackets
+pub fn split_address_list(header: &str) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut in_angle = false;
+    let mut escaped = false;
+
+    for c in header.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            '<' if !in_quotes => in_angle = true,
+            '>' if !in_quotes => in_angle = false,
+            ',' | ';' if !in_quotes && !in_angle => {
+                result.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    result.push(current.trim().to_string());
+
+    // An empty group such as "undisclosed-recipients:;" names no mailbox
+    let is_empty_group = |a: &str| a.ends_with(':') && !a.contains('@');
+    result.into_iter().filter(|a| !a.is_empty() && !is_empty_group(a)).collect()
+}
+
+// Display name (unquoted and unescaped) and address of a single mailbox
+fn split_mailbox(value: &str) -> (Option<String>, String) {
+    let value = value.trim();
+    match (value.rfind('<'), value.rfind('>')) {
+        (Some(start), Some(end)) if start < end => {
+            (Some(unquote(value[..start].trim())), value[start + 1..end].trim().to_string())
+        }
+        _ => (None, value.to_string()),
+    }
+}
+
+fn unquote(name: &str) -> String {
+    let Some(inner) = name.strip_prefix('"').and_then(|n| n.strip_suffix('"')) else {
+        return name.to_string();
+    };
+    let mut result = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => result.extend(chars.next()),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+fn validate_mailbox(input: &str) -> Result<EmailAddress, String> {
+    if input.chars().any(char::is_control) {
+        return Err("contains a line break or control character".to_string());
+    }
+    let (name, address) = split_mailbox(input);
+    if input.contains('<') && !input.trim_end().ends_with('>') {
+        return Err("unexpected text after '>'".to_string());
+    }
+    let address = validate_address(&address)?;
+    Ok(EmailAddress::from_parts(name.as_deref(), Some(&address)))
+}
+
+// The address with its domain lowercased and converted to punycode
+fn validate_address(address: &str) -> Result<String, String> {
+    let (local, domain) = address.rsplit_once('@').ok_or("missing '@'")?;
+
+    if local.is_empty() || local.len() > MAX_LOCAL_PART_LENGTH {
+        return Err(format!("local part must be 1-{} characters", MAX_LOCAL_PART_LENGTH));
+    }
+    let is_atext = |c: char| c.is_ascii_alphanumeric() || "!#$%&'*+-/=?^_`{|}~".contains(c);
+    if !local.split('.').all(|atom| !atom.is_empty() && atom.chars().all(is_atext)) {
+        return Err(format!("invalid local part '{}'", local));
+    }
+
+    let domain = idna::domain_to_ascii(domain).map_err(|_| format!("invalid domain '{}'", domain))?;
+    let labels: Vec<&str> = domain.split('.').collect();
+    if labels.len() < 2 {
+        return Err(format!("domain '{}' needs a top-level domain", domain));
+    }
+    let valid_label = |label: &&str| {
+        !label.is_empty()
+            && label.len() <= MAX_DOMAIN_LABEL_LENGTH
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    };
+    if !labels.iter().all(valid_label) || labels.last().is_some_and(|tld| tld.chars().all(|c| c.is_ascii_digit())) {
+        return Err(format!("invalid domain '{}'", domain));
+    }
+
+    let address = format!("{}@{}", local, domain);
+    if address.len() > MAX_ADDRESS_LENGTH {
+        return Err(format!("longer than {} characters", MAX_ADDRESS_LENGTH));
+    }
+    Ok(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn splits_outside_quotes_and_angle_brackets() {
+        assert_eq!(
+            split_address_list(r#""Doe, John" <john@example.com>; anna@example.com, <odd,local@example.com>,,"#),
+            strings(&[r#""Doe, John" <john@example.com>"#, "anna@example.com", "<odd,local@example.com>"])
+        );
+        assert_eq!(split_address_list(r#""a \" , b" <ab@example.com>"#), strings(&[r#""a \" , b" <ab@example.com>"#]));
+        assert!(split_address_list(" , ; ").is_empty());
+        assert!(split_address_list("undisclosed-recipients:;").is_empty());
+    }
+
+    #[test]
+    fn unquotes_and_unescapes_display_names() {
+        assert_eq!(unquote(r#""Doe, John""#), "Doe, John");
+        assert_eq!(unquote(r#""Say \"hi\" \\ bye""#), r#"Say "hi" \ bye"#);
+        assert_eq!(unquote("Plain Name"), "Plain Name");
+        assert_eq!(unquote(r#""unterminated"#), r#""unterminated"#);
+    }
+
+    #[test]
+    fn parses_and_displays_quoted_names() {
+        let address = EmailAddress::parse(r#""Doe, \"JD\" John" <john@example.com>"#);
+        assert_eq!(address.name.as_deref(), Some(r#"Doe, "JD" John"#));
+        assert_eq!(address.address, "john@example.com");
+        assert_eq!(address.to_string(), r#""Doe, \"JD\" John" <john@example.com>"#);
+
+        // Graph repeats the address as the name
+        let address = EmailAddress::from_parts(Some("JOHN@example.com"), Some("john@example.com"));
+        assert_eq!(address.name, None);
+        assert_eq!(address.to_string(), "john@example.com");
+    }
+
+    #[test]
+    fn converts_idn_domains_to_punycode() {
+        assert_eq!(validate_address("info@bücher.de").unwrap(), "info@xn--bcher-kva.de");
+        assert_eq!(validate_address("user@Пример.РФ").unwrap(), "user@xn--e1afmkfd.xn--p1ai");
+        assert_eq!(validate_address("John.Doe+tag@Example.COM").unwrap(), "John.Doe+tag@example.com");
+    }
+
+    #[test]
+    fn rejects_invalid_addresses() {
+        for address in [
+            "no-at-sign",
+            "@example.com",
+            "john@localhost",
+            "john..doe@example.com",
+            ".john@example.com",
+            "jo hn@example.com",
+            "john@-example.com",
+            "john@example.123",
+            "john@exa_mple.com",
+        ] {
+            assert!(validate_address(address).is_err(), "{}", address);
+        }
+        assert!(validate_address(&format!("{}@example.com", "a".repeat(65))).is_err());
+        assert!(validate_address(&format!("john@{}.com", "a".repeat(64))).is_err());
+        assert!(validate_mailbox("John <john@example.com> trailing").is_err());
+        assert!(validate_mailbox("john@example.com\r\nBcc: x@example.com").is_err());
+    }
+
+    #[test]
+    fn normalizes_and_dedupes_across_fields() {
+        let recipients = normalize_recipients(
+            &strings(&["Anna <ANNA@example.com>", "bob@example.com; anna@example.com"]),
+            &strings(&["Bob <bob@EXAMPLE.com>", "carol@example.com"]),
+            &strings(&["carol@example.com", "dave@example.com", "dave@example.com"]),
+            &strings(&["anna@example.com", "Anna <anna@example.com>"]),
+        ).unwrap();
+
+        let addresses = |list: &[EmailAddress]| list.iter().map(|a| a.address.clone()).collect::<Vec<_>>();
+        assert_eq!(addresses(&recipients.to), strings(&["ANNA@example.com", "bob@example.com"]));
+        assert_eq!(recipients.to[0].name.as_deref(), Some("Anna"));
+        assert_eq!(addresses(&recipients.cc), strings(&["carol@example.com"]));
+        assert_eq!(addresses(&recipients.bcc), strings(&["dave@example.com"]));
+        // Reply-To is only deduplicated within itself
+        assert_eq!(addresses(&recipients.reply_to), strings(&["anna@example.com"]));
+    }
+
+    #[test]
+    fn reports_every_invalid_recipient() {
+        let Err(AppError::InvalidAddresses(errors)) = normalize_recipients(
+            &strings(&["ok@example.com", "broken"]),
+            &strings(&["also@broken"]),
+            &[],
+            &[],
+        ) else {
+            panic!("expected InvalidAddresses");
+        };
+
+        let fields: Vec<(&str, &str)> = errors.iter().map(|e| (e.field, e.input.as_str())).collect();
+        assert_eq!(fields, vec![("to", "broken"), ("cc", "also@broken")]);
+    }
+}
@@ -9,12 +9,14 @@ use serde_json::json;
 use regex::Regex;
 use crate::error::AppError;
 use crate::state::AppState;
-use super::provider::{EmailProvider, ListParams, SendMessageRequest, BatchModifyRequest};
+use super::provider::{EmailProvider, ListParams, SendMessageRequest, BatchModifyRequest, BatchItemStatus, LabelRequest, AttachmentContent, MessageFormat, ReplyMode, ReplyRequest, SYSTEM_LABELS, build_label_tree};
+use super::mime::sanitize_header_text;
 use super::gmail::GmailProvider;
 use super::outlook::OutlookProvider;
 use crate::middleware::auth::AuthLevel;
 use crate::handlers::postmark::PostmarkProvider;
 use crate::services::bubble::BubbleService;
+use crate::services::signing::Signer;
 
 #[derive(Deserialize)]
 pub struct ProviderParams {
@@ -74,23 +76,259 @@ pub async fn list_messages(
     let token = get_token(&headers)?;
     let provider = get_provider(&provider_params, state.client.clone());
     
-    let result: serde_json::Value = provider.list_messages(token, list_params).await?;
+    let result = provider.list_messages(token, list_params).await?;
     Ok(Json(result).into_response())
 }
 
+#[derive(Deserialize)]
+pub struct MessageParams {
+    #[serde(default)]
+    pub format: MessageFormat, // "raw" opts into downloading the full MIME source (Gmail)
+}
+
 pub async fn get_message(
     State(state): State<AppState>,
     headers: HeaderMap,
     Path(id): Path<String>,
     Query(provider_params): Query<ProviderParams>,
+    Query(message_params): Query<MessageParams>,
+) -> Result<Response, AppError> {
+    let token = get_token(&headers)?;
+    let provider = get_provider(&provider_params, state.client.clone());
+    
+    let result: super::provider::CleanMessage = provider.get_message(token, &id, message_params.format).await?;
+    Ok(Json(result).into_response())
+}
+
+pub async fn get_thread(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(provider_params): Query<ProviderParams>,
+) -> Result<Response, AppError> {
+    let token = get_token(&headers)?;
+    let provider = get_provider(&provider_params, state.client.clone());
+    
+    let result: super::provider::Thread = provider.get_thread(token, &id).await?;
+    Ok(Json(result).into_response())
+}
+
+// --- Attachments ---
+
+// Default and maximum lifetime of a signed attachment link, in seconds
+const ATTACHMENT_LINK_TTL: u64 = 300;
+const ATTACHMENT_LINK_MAX_TTL: u64 = 3600;
+
+#[derive(Deserialize)]
+pub struct AttachmentParams {
+    pub disposition: Option<String>, // "inline" or "attachment" (default)
+}
+
+pub async fn get_attachment(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((id, attachment_id)): Path<(String, String)>,
+    Query(provider_params): Query<ProviderParams>,
+    Query(params): Query<AttachmentParams>,
 ) -> Result<Response, AppError> {
     let token = get_token(&headers)?;
     let provider = get_provider(&provider_params, state.client.clone());
     
-    let result: super::provider::CleanMessage = provider.get_message(token, &id).await?;
+    let content = provider.get_attachment(token, &id, &attachment_id).await?;
+    attachment_response(content, params.disposition.as_deref().unwrap_or("attachment"))
+}
+
+/// RFC 822 source of a message, e.g. for archiving correspondence as .eml
+pub async fn get_raw_message(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(provider_params): Query<ProviderParams>,
+) -> Result<Response, AppError> {
+    let token = get_token(&headers)?;
+    let provider = get_provider(&provider_params, state.client.clone());
+
+    let raw = provider.get_raw_message(token, &id).await?;
+    attachment_response(AttachmentContent {
+        filename: format!("{}.eml", id),
+        mime_type: "message/rfc822".to_string(),
+        size: Some(raw.len() as u64),
+        body: axum::body::Body::from(raw),
+    }, "attachment")
+}
+
+/// Upper bound for uploaded .eml files, well below Gmail's and Graph's own limits
+pub const MAX_IMPORT_SIZE: usize = 25 * 1024 * 1024;
+
+#[derive(Deserialize)]
+pub struct ImportParams {
+    pub label_id: Option<String>, // Target label/folder, INBOX by default
+}
+
+/// Inserts an uploaded .eml (raw request body) into the mailbox without sending it
+pub async fn import_message(
+    State(state): State<AppState>,
+    Extension(auth_level): Extension<AuthLevel>,
+    headers: HeaderMap,
+    Query(provider_params): Query<ProviderParams>,
+    Query(params): Query<ImportParams>,
+    body: axum::body::Bytes,
+) -> Result<Response, AppError> {
+    if auth_level != AuthLevel::Admin {
+        return Err(AppError::Forbidden("This endpoint requires administrator privileges".to_string()));
+    }
+    let token = get_token(&headers)?;
+    let provider = get_provider(&provider_params, state.client.clone());
+
+    if body.is_empty() {
+        return Err(AppError::BadRequest("Request body must contain the message in RFC 822 (.eml) format".to_string()));
+    }
+    let label_id = params.label_id.as_deref().filter(|l| !l.is_empty()).unwrap_or("INBOX");
+
+    let result = provider.import_message(token, body.to_vec(), label_id).await?;
     Ok(Json(result).into_response())
 }
 
+#[derive(Deserialize)]
+pub struct AttachmentLinkParams {
+    pub ttl: Option<u64>,
+}
+
+/// Mints a short-lived URL for an attachment that can be opened without the
+/// Bearer token or API key (e.g. a PDF in a new browser tab).
+pub async fn create_attachment_link(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((id, attachment_id)): Path<(String, String)>,
+    Query(provider_params): Query<ProviderParams>,
+    Query(params): Query<AttachmentLinkParams>,
+) -> Result<Response, AppError> {
+    let token = get_token(&headers)?;
+    let provider = provider_params.provider.as_deref().unwrap_or("gmail");
+    let ttl = params.ttl.unwrap_or(ATTACHMENT_LINK_TTL).clamp(1, ATTACHMENT_LINK_MAX_TTL);
+    let expires = unix_now() + ttl;
+
+    let signer = Signer::new(&state.config.signing_key);
+    // The OAuth token travels inside the link, so it is encrypted, never just signed
+    let sealed_token = signer.seal(token.as_bytes());
+    let sig = signer.sign(&attachment_link_payload(provider, &id, &attachment_id, expires, &sealed_token));
+
+    let url = format!(
+        "/api/attachments/signed?provider={}&message_id={}&attachment_id={}&expires={}&token={}&sig={}",
+        urlencoding::encode(provider),
+        urlencoding::encode(&id),
+        urlencoding::encode(&attachment_id),
+        expires,
+        sealed_token,
+        sig
+    );
+
+    Ok(Json(json!({
+        "url": url,
+        "expires_at": expires
+    })).into_response())
+}
+
+#[derive(Deserialize)]
+pub struct SignedAttachmentParams {
+    pub provider: String,
+    pub message_id: String,
+    pub attachment_id: String,
+    pub expires: u64,
+    pub token: String,
+    pub sig: String,
+    pub disposition: Option<String>,
+}
+
+/// Serves an attachment from a link minted by `create_attachment_link`.
+/// Mounted outside the API key middleware: the signature is the credential.
+pub async fn get_signed_attachment(
+    State(state): State<AppState>,
+    Query(params): Query<SignedAttachmentParams>,
+) -> Result<Response, AppError> {
+    let signer = Signer::new(&state.config.signing_key);
+    let payload = attachment_link_payload(&params.provider, &params.message_id, &params.attachment_id, params.expires, &params.token);
+
+    if !signer.verify(&payload, &params.sig) {
+        return Err(AppError::Forbidden("Invalid link signature".to_string()));
+    }
+    if params.expires < unix_now() {
+        return Err(AppError::Forbidden("Link has expired".to_string()));
+    }
+
+    let token = signer.open(&params.token)
+        .and_then(|t| String::from_utf8(t).ok())
+        .ok_or_else(|| AppError::Forbidden("Invalid link token".to_string()))?;
+
+    let provider_params = ProviderParams {
+        provider: Some(params.provider),
+        company: None,
+    };
+    let provider = get_provider(&provider_params, state.client.clone());
+
+    let content = provider.get_attachment(&token, &params.message_id, &params.attachment_id).await?;
+    attachment_response(content, params.disposition.as_deref().unwrap_or("inline"))
+}
+
+fn attachment_link_payload(provider: &str, message_id: &str, attachment_id: &str, expires: u64, token: &str) -> String {
+    format!("{}\n{}\n{}\n{}\n{}", provider, message_id, attachment_id, expires, token)
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Types a browser can display inline without running script from the proxy's origin
+const INLINE_SAFE_TYPES: [&str; 7] = [
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/webp",
+    "image/bmp",
+    "image/avif",
+    "application/pdf",
+];
+
+fn attachment_response(content: AttachmentContent, disposition: &str) -> Result<Response, AppError> {
+    // HTML, SVG and anything unknown are always downloaded, never rendered
+    let essence = content.mime_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    let disposition = if disposition == "inline" && INLINE_SAFE_TYPES.contains(&essence.as_str()) {
+        "inline"
+    } else {
+        "attachment"
+    };
+
+    // Plain ASCII fallback for old clients plus RFC 6266 filename* for the real name
+    let ascii_name: String = content.filename
+        .chars()
+        .map(|c| if (c.is_ascii_graphic() && c != '"' && c != '\\') || c == ' ' { c } else { '_' })
+        .collect();
+    let content_disposition = format!(
+        "{}; filename=\"{}\"; filename*=UTF-8''{}",
+        disposition,
+        ascii_name,
+        urlencoding::encode(&content.filename)
+    );
+
+    let mut builder = axum::response::Response::builder()
+        .header("Content-Type", content.mime_type)
+        .header("Content-Disposition", content_disposition)
+        .header("Cache-Control", "private, no-store")
+        .header("X-Content-Type-Options", "nosniff")
+        .header("Content-Security-Policy", "sandbox");
+
+    if let Some(size) = content.size {
+        builder = builder.header("Content-Length", size);
+    }
+
+    builder
+        .body(content.body)
+        .map_err(|e| AppError::Internal(anyhow::anyhow!("Failed to build attachment response: {}", e)))
+}
+
 pub async fn send_message(
     State(state): State<AppState>,
     Extension(auth_level): Extension<AuthLevel>,
@@ -104,22 +342,349 @@ pub async fn send_message(
     let token = get_token(&headers)?;
     let provider = get_provider(&provider_params, state.client.clone());
     
+    let payload = payload.sanitize_headers()?.require_recipients()?.inline_remote_images().await?;
     let result: serde_json::Value = provider.send_message(token, payload).await?;
     Ok(Json(result).into_response())
 }
 
+// --- Reply / Forward ---
+
+async fn send_reply(
+    state: AppState,
+    auth_level: AuthLevel,
+    headers: HeaderMap,
+    id: String,
+    provider_params: ProviderParams,
+    mode: ReplyMode,
+    payload: ReplyRequest,
+) -> Result<Response, AppError> {
+    if auth_level != AuthLevel::Admin {
+        return Err(AppError::Forbidden("This endpoint requires administrator privileges".to_string()));
+    }
+    let token = get_token(&headers)?;
+    let provider = get_provider(&provider_params, state.client.clone());
+
+    let result: serde_json::Value = provider.reply(token, &id, mode, payload.sanitize_headers()?).await?;
+    Ok(Json(result).into_response())
+}
+
+pub async fn reply_message(
+    State(state): State<AppState>,
+    Extension(auth_level): Extension<AuthLevel>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(provider_params): Query<ProviderParams>,
+    Json(payload): Json<ReplyRequest>,
+) -> Result<Response, AppError> {
+    send_reply(state, auth_level, headers, id, provider_params, ReplyMode::Reply, payload).await
+}
+
+pub async fn reply_all_message(
+    State(state): State<AppState>,
+    Extension(auth_level): Extension<AuthLevel>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(provider_params): Query<ProviderParams>,
+    Json(payload): Json<ReplyRequest>,
+) -> Result<Response, AppError> {
+    send_reply(state, auth_level, headers, id, provider_params, ReplyMode::ReplyAll, payload).await
+}
+
+pub async fn forward_message(
+    State(state): State<AppState>,
+    Extension(auth_level): Extension<AuthLevel>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(provider_params): Query<ProviderParams>,
+    Json(payload): Json<ReplyRequest>,
+) -> Result<Response, AppError> {
+    send_reply(state, auth_level, headers, id, provider_params, ReplyMode::Forward, payload).await
+}
+
+// --- Message state ---
+
+enum MessageAction {
+    MarkRead,
+    MarkUnread,
+    Star,
+    Unstar,
+    Archive,
+    Trash,
+    Restore,
+    Delete,
+}
+
+async fn apply_message_action(
+    state: AppState,
+    auth_level: AuthLevel,
+    headers: HeaderMap,
+    id: String,
+    provider_params: ProviderParams,
+    action: MessageAction,
+) -> Result<Response, AppError> {
+    if auth_level != AuthLevel::Admin {
+        return Err(AppError::Forbidden("This endpoint requires administrator privileges".to_string()));
+    }
+    let token = get_token(&headers)?;
+    let provider = get_provider(&provider_params, state.client.clone());
+
+    match action {
+        MessageAction::MarkRead => provider.mark_read(token, &id, true).await?,
+        MessageAction::MarkUnread => provider.mark_read(token, &id, false).await?,
+        MessageAction::Star => provider.set_starred(token, &id, true).await?,
+        MessageAction::Unstar => provider.set_starred(token, &id, false).await?,
+        MessageAction::Archive => provider.archive(token, &id).await?,
+        MessageAction::Trash => provider.trash(token, &id).await?,
+        MessageAction::Restore => provider.restore(token, &id).await?,
+        MessageAction::Delete => provider.delete_message(token, &id).await?,
+    }
+    Ok(Json(json!({"status": "ok"})).into_response())
+}
+
+pub async fn mark_read(
+    State(state): State<AppState>,
+    Extension(auth_level): Extension<AuthLevel>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(provider_params): Query<ProviderParams>,
+) -> Result<Response, AppError> {
+    apply_message_action(state, auth_level, headers, id, provider_params, MessageAction::MarkRead).await
+}
+
+pub async fn mark_unread(
+    State(state): State<AppState>,
+    Extension(auth_level): Extension<AuthLevel>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(provider_params): Query<ProviderParams>,
+) -> Result<Response, AppError> {
+    apply_message_action(state, auth_level, headers, id, provider_params, MessageAction::MarkUnread).await
+}
+
+pub async fn star_message(
+    State(state): State<AppState>,
+    Extension(auth_level): Extension<AuthLevel>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(provider_params): Query<ProviderParams>,
+) -> Result<Response, AppError> {
+    apply_message_action(state, auth_level, headers, id, provider_params, MessageAction::Star).await
+}
+
+pub async fn unstar_message(
+    State(state): State<AppState>,
+    Extension(auth_level): Extension<AuthLevel>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(provider_params): Query<ProviderParams>,
+) -> Result<Response, AppError> {
+    apply_message_action(state, auth_level, headers, id, provider_params, MessageAction::Unstar).await
+}
+
+pub async fn archive_message(
+    State(state): State<AppState>,
+    Extension(auth_level): Extension<AuthLevel>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(provider_params): Query<ProviderParams>,
+) -> Result<Response, AppError> {
+    apply_message_action(state, auth_level, headers, id, provider_params, MessageAction::Archive).await
+}
+
+pub async fn trash_message(
+    State(state): State<AppState>,
+    Extension(auth_level): Extension<AuthLevel>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(provider_params): Query<ProviderParams>,
+) -> Result<Response, AppError> {
+    apply_message_action(state, auth_level, headers, id, provider_params, MessageAction::Trash).await
+}
+
+pub async fn restore_message(
+    State(state): State<AppState>,
+    Extension(auth_level): Extension<AuthLevel>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(provider_params): Query<ProviderParams>,
+) -> Result<Response, AppError> {
+    apply_message_action(state, auth_level, headers, id, provider_params, MessageAction::Restore).await
+}
+
+pub async fn delete_message(
+    State(state): State<AppState>,
+    Extension(auth_level): Extension<AuthLevel>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(provider_params): Query<ProviderParams>,
+) -> Result<Response, AppError> {
+    apply_message_action(state, auth_level, headers, id, provider_params, MessageAction::Delete).await
+}
+
+// --- Drafts ---
+
+#[derive(Deserialize)]
+pub struct DraftListParams {
+    pub max_results: Option<u32>,
+}
+
+pub async fn list_drafts(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(provider_params): Query<ProviderParams>,
+    Query(params): Query<DraftListParams>,
+) -> Result<Response, AppError> {
+    let token = get_token(&headers)?;
+    let provider = get_provider(&provider_params, state.client.clone());
+    
+    let result = provider.list_drafts(token, params.max_results).await?;
+    Ok(Json(result).into_response())
+}
+
+pub async fn create_draft(
+    State(state): State<AppState>,
+    Extension(auth_level): Extension<AuthLevel>,
+    headers: HeaderMap,
+    Query(provider_params): Query<ProviderParams>,
+    Json(payload): Json<SendMessageRequest>,
+) -> Result<Response, AppError> {
+    if auth_level != AuthLevel::Admin {
+        return Err(AppError::Forbidden("This endpoint requires administrator privileges".to_string()));
+    }
+    let token = get_token(&headers)?;
+    let provider = get_provider(&provider_params, state.client.clone());
+    
+    let payload = payload.sanitize_headers()?.inline_remote_images().await?;
+    let result = provider.create_draft(token, payload).await?;
+    Ok(Json(result).into_response())
+}
+
+pub async fn update_draft(
+    State(state): State<AppState>,
+    Extension(auth_level): Extension<AuthLevel>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(provider_params): Query<ProviderParams>,
+    Json(payload): Json<SendMessageRequest>,
+) -> Result<Response, AppError> {
+    if auth_level != AuthLevel::Admin {
+        return Err(AppError::Forbidden("This endpoint requires administrator privileges".to_string()));
+    }
+    let token = get_token(&headers)?;
+    let provider = get_provider(&provider_params, state.client.clone());
+    
+    let payload = payload.sanitize_headers()?.inline_remote_images().await?;
+    let result = provider.update_draft(token, &id, payload).await?;
+    Ok(Json(result).into_response())
+}
+
+pub async fn send_draft(
+    State(state): State<AppState>,
+    Extension(auth_level): Extension<AuthLevel>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(provider_params): Query<ProviderParams>,
+) -> Result<Response, AppError> {
+    if auth_level != AuthLevel::Admin {
+        return Err(AppError::Forbidden("This endpoint requires administrator privileges".to_string()));
+    }
+    let token = get_token(&headers)?;
+    let provider = get_provider(&provider_params, state.client.clone());
+    
+    let result: serde_json::Value = provider.send_draft(token, &id).await?;
+    Ok(Json(result).into_response())
+}
+
+#[derive(Deserialize)]
+pub struct LabelListParams {
+    #[serde(default)]
+    pub counts: bool, // unread/total counts; Outlook always has them, Gmail fetches them per label
+}
+
 pub async fn list_labels(
     State(state): State<AppState>,
     headers: HeaderMap,
     Query(provider_params): Query<ProviderParams>,
+    Query(params): Query<LabelListParams>,
 ) -> Result<Response, AppError> {
     let token = get_token(&headers)?;
     let provider = get_provider(&provider_params, state.client.clone());
     
-    let result = provider.list_labels(token).await?;
+    let result = provider.list_labels(token, params.counts).await?;
+    Ok(Json(result).into_response())
+}
+
+pub async fn get_label_tree(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(provider_params): Query<ProviderParams>,
+    Query(params): Query<LabelListParams>,
+) -> Result<Response, AppError> {
+    let token = get_token(&headers)?;
+    let provider = get_provider(&provider_params, state.client.clone());
+
+    let labels = provider.list_labels(token, params.counts).await?;
+    Ok(Json(build_label_tree(labels)).into_response())
+}
+
+pub async fn create_label(
+    State(state): State<AppState>,
+    Extension(auth_level): Extension<AuthLevel>,
+    headers: HeaderMap,
+    Query(provider_params): Query<ProviderParams>,
+    Json(payload): Json<LabelRequest>,
+) -> Result<Response, AppError> {
+    if auth_level != AuthLevel::Admin {
+        return Err(AppError::Forbidden("This endpoint requires administrator privileges".to_string()));
+    }
+    let token = get_token(&headers)?;
+    let provider = get_provider(&provider_params, state.client.clone());
+
+    let result = provider.create_label(token, payload).await?;
     Ok(Json(result).into_response())
 }
 
+pub async fn update_label(
+    State(state): State<AppState>,
+    Extension(auth_level): Extension<AuthLevel>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(provider_params): Query<ProviderParams>,
+    Json(payload): Json<LabelRequest>,
+) -> Result<Response, AppError> {
+    if auth_level != AuthLevel::Admin {
+        return Err(AppError::Forbidden("This endpoint requires administrator privileges".to_string()));
+    }
+    if SYSTEM_LABELS.contains(&id.as_str()) {
+        return Err(AppError::BadRequest("System labels cannot be changed".to_string()));
+    }
+    let token = get_token(&headers)?;
+    let provider = get_provider(&provider_params, state.client.clone());
+
+    let result = provider.update_label(token, &id, payload).await?;
+    Ok(Json(result).into_response())
+}
+
+pub async fn delete_label(
+    State(state): State<AppState>,
+    Extension(auth_level): Extension<AuthLevel>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(provider_params): Query<ProviderParams>,
+) -> Result<Response, AppError> {
+    if auth_level != AuthLevel::Admin {
+        return Err(AppError::Forbidden("This endpoint requires administrator privileges".to_string()));
+    }
+    if SYSTEM_LABELS.contains(&id.as_str()) {
+        return Err(AppError::BadRequest("System labels cannot be changed".to_string()));
+    }
+    let token = get_token(&headers)?;
+    let provider = get_provider(&provider_params, state.client.clone());
+
+    provider.delete_label(token, &id).await?;
+    Ok(Json(json!({"status": "ok"})).into_response())
+}
+
 pub async fn batch_modify_labels(
     State(state): State<AppState>,
     Extension(auth_level): Extension<AuthLevel>,
@@ -133,8 +698,20 @@ pub async fn batch_modify_labels(
     let token = get_token(&headers)?;
     let provider = get_provider(&provider_params, state.client.clone());
     
-    provider.batch_modify_labels(token, payload).await?;
-    Ok(Json(json!({"status": "ok"})).into_response())
+    let results = provider.batch_modify_labels(token, payload).await?;
+
+    let succeeded = results.iter().filter(|r| r.status == BatchItemStatus::Succeeded).count();
+    let failed = results.iter().filter(|r| r.status == BatchItemStatus::Failed).count();
+    let skipped = results.len() - succeeded - failed;
+    let status = if failed == 0 { "ok" } else if succeeded > 0 { "partial" } else { "failed" };
+
+    Ok(Json(json!({
+        "status": status,
+        "succeeded": succeeded,
+        "failed": failed,
+        "skipped": skipped,
+        "results": results,
+    })).into_response())
 }
 
 pub async fn get_profile(
@@ -194,6 +771,22 @@ pub struct SendQuoteRequest {
     pub maildata_identificator: Option<String>,
     pub company: Option<String>,
     pub trigger_reminder: Option<bool>,
+    #[serde(default)]
+    pub inline_images: bool, // Embed the images of the Bubble HTML as inline attachments
+}
+
+impl SendQuoteRequest {
+    // The outgoing message without body and PDF, validated like any send before a Bubble workflow runs
+    fn message_request(&self) -> Result<SendMessageRequest, AppError> {
+        SendMessageRequest {
+            to: self.to.clone(),
+            cc: self.cc.clone(),
+            subject: self.subject.clone(),
+            thread_id: self.thread_id.clone(),
+            inline_images: self.inline_images,
+            ..Default::default()
+        }.sanitize_headers()?.require_recipients()
+    }
 }
 
 pub async fn send_quote_email(
@@ -206,6 +799,7 @@ pub async fn send_quote_email(
         return Err(AppError::Forbidden("This endpoint requires administrator privileges".to_string()));
     }
     let token = get_token(&headers)?;
+    let mut message = req.message_request()?;
     
     // 1. Setup Services
     let bubble_service = BubbleService::new(state.client.clone())?;
@@ -308,19 +902,21 @@ pub async fn send_quote_email(
         &req.quote_id,
         req.version.as_deref(),
         &filename,
-        req.to.clone(),
-        req.cc.clone().unwrap_or_default(),
-        &req.subject,
+        message.to.clone(),
+        message.cc.clone().unwrap_or_default(),
+        &message.subject,
         req.maildata_identificator.as_deref().unwrap_or(""),
         req.pdf_export_settings.clone().unwrap_or_default(),
         pdf_url_to_pass,
     ).await?;
     
-    // 4. Attach PDF
-    let attachments = Some(vec![super::provider::Attachment {
-        filename,
+    // 4. Attach PDF (the name comes from the client or Bubble and ends up in a header)
+    message.body = html_body;
+    message.attachments = Some(vec![super::provider::Attachment {
+        filename: sanitize_header_text("attachment filename", &filename)?,
         content: pdf_bytes,
         mime_type: "application/pdf".to_string(),
+        content_id: None,
     }]);
 
     // 5. Select Provider
@@ -331,14 +927,7 @@ pub async fn send_quote_email(
         _ => return Err(AppError::BadRequest("Invalid provider. Use 'gmail', 'outlook', or 'postmark'".to_string())),
     };
     
-    let send_req = SendMessageRequest {
-        to: req.to,
-        cc: req.cc,
-        subject: req.subject,
-        body: html_body, 
-        thread_id: req.thread_id,
-        attachments, 
-    };
+    let send_req = message.inline_remote_images().await?;
     
     let result: serde_json::Value = provider_instance.send_message(token, send_req).await?;
     
@@ -389,9 +978,9 @@ pub async fn reminder_webhook(
     };
 
     // 2. Get Token (Optional for Postmark)
-    let token = match req.keys.as_deref() {
+    let token = match req.keys.clone() {
         Some(t) => t,
-        None if req.platform == "postmark" => "", // Use empty string, provider will use fallback token
+        None if req.platform == "postmark" => String::new(), // Use empty string, provider will use fallback token
         None => return Err(AppError::BadRequest("API Key (keys) is required for reminder webhook".to_string())),
     };
 
@@ -414,7 +1003,7 @@ pub async fn reminder_webhook(
             .map_err(|e| AppError::BadGateway(format!("Failed to read file bytes: {}", e)))?
             .to_vec();
             
-        (bytes, req.file_name)
+        (bytes, req.file_name.clone())
     } else {
         // Assume Base64
         use base64::{Engine as _, engine::general_purpose::STANDARD};
@@ -426,13 +1015,14 @@ pub async fn reminder_webhook(
         
         let bytes = STANDARD.decode(clean_base64.trim())
             .map_err(|e| AppError::BadRequest(format!("Invalid file base64: {}", e)))?;
-        (bytes, req.file_name)
+        (bytes, req.file_name.clone())
     };
 
     let attachments = Some(vec![super::provider::Attachment {
         filename,
         content: file_bytes,
         mime_type: "application/pdf".to_string(),
+        content_id: None,
     }]);
 
     // 4. Select Provider
@@ -447,20 +1037,27 @@ pub async fn reminder_webhook(
     let content_len = req.content.len();
     tracing::info!("Reminder webhook: processing content ({} bytes)", content_len);
 
-    let send_req = SendMessageRequest {
-        to: req.recipients,
-        cc: req.cc,
-        subject: req.subject,
-        body: req.content,
-        thread_id: None,
-        attachments,
-    };
+    let send_req = req.into_send_request(attachments)?;
 
-    let result: serde_json::Value = provider_instance.send_message(token, send_req).await?;
+    let result: serde_json::Value = provider_instance.send_message(&token, send_req).await?;
 
     Ok(Json(result).into_response())
 }
 
+impl ReminderWebhookRequest {
+    fn into_send_request(self, attachments: Option<Vec<super::provider::Attachment>>) -> Result<SendMessageRequest, AppError> {
+        SendMessageRequest {
+            to: self.recipients,
+            cc: self.cc,
+            subject: self.subject,
+            body: self.content,
+            thread_id: None,
+            attachments,
+            ..Default::default()
+        }.sanitize_headers()?.require_recipients()
+    }
+}
+
 /// A very pragmatic fallback parser for malformed JSON caused by unescaped HTML in the "content" field.
 /// This looks for standard keys and tries to isolate the "content" block.
 fn try_parse_malformed_reminder_json(body: &str) -> Option<ReminderWebhookRequest> {
@@ -580,3 +1177,65 @@ pub async fn get_embed_js(
         .body(axum::body::Body::from(js))
         .unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::provider::Attachment;
+
+    fn quote_request(subject: &str, to: &str) -> SendQuoteRequest {
+        serde_json::from_value(json!({
+            "quote_id": "q1",
+            "provider": "gmail",
+            "to": [to],
+            "cc": ["Sales <sales@example.com>"],
+            "subject": subject,
+        })).unwrap()
+    }
+
+    #[test]
+    fn quote_sanitizes_headers_before_bubble_runs() {
+        let req = quote_request("Quote\r\nBcc: victim@example.com", "anna@example.com").message_request().unwrap();
+        assert_eq!(req.subject, "Quote Bcc: victim@example.com");
+        assert_eq!(req.cc.unwrap(), vec!["\"Sales\" <sales@example.com>"]);
+
+        let invalid = quote_request("Quote", "anna@example.com\r\nBcc: victim@example.com").message_request();
+        assert!(matches!(invalid, Err(AppError::InvalidAddresses(_))));
+    }
+
+    #[test]
+    fn quote_requires_a_recipient() {
+        let mut req = quote_request("Quote", " ");
+        req.cc = None;
+        assert!(matches!(req.message_request(), Err(AppError::BadRequest(_))));
+    }
+
+    fn reminder_request(subject: &str, recipient: &str) -> ReminderWebhookRequest {
+        serde_json::from_value(json!({
+            "content": "<p>Reminder</p>",
+            "subject": subject,
+            "recipients": [recipient],
+            "file": "JVBERg==",
+            "file_name": "quote.pdf",
+            "platform": "gmail",
+        })).unwrap()
+    }
+
+    #[test]
+    fn reminder_sanitizes_headers_and_attachment_name() {
+        let attachment = Attachment {
+            filename: "quote\r\n.pdf".to_string(),
+            content: b"%PDF".to_vec(),
+            mime_type: "application/pdf".to_string(),
+            content_id: None,
+        };
+        let req = reminder_request("Reminder\nBcc: victim@example.com", "anna@example.com")
+            .into_send_request(Some(vec![attachment]))
+            .unwrap();
+        assert_eq!(req.subject, "Reminder Bcc: victim@example.com");
+        assert_eq!(req.attachments.unwrap()[0].filename, "quote .pdf");
+
+        let invalid = reminder_request("Reminder", "anna@example.com\nBcc: victim@example.com").into_send_request(None);
+        assert!(matches!(invalid, Err(AppError::InvalidAddresses(_))));
+    }
+}
@@ -1,23 +1,15 @@
 use serde_json::json;
 use reqwest::Client;
 use crate::error::AppError;
-use mail_parser::{MessageParser, MimeHeaders};
+use mail_parser::{Message, MessageParser, MimeHeaders, PartType};
 use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
-// --- Global Pagination Cache ---
-use std::sync::{Mutex};
-use std::sync::OnceLock;
-use lru::LruCache;
-use std::num::NonZeroUsize;
+use std::collections::HashMap;
 
-use super::provider::{EmailProvider, CleanMessage, MessageSummary, AttachmentSummary, SendMessageRequest, ListParams, Label, BatchModifyRequest};
-
-// Key for the cache: (Google Token Hash + Query Params Hash) -> Page Number -> Gmail Token
-// Fixed Point 7: Use LRU cache to prevent memory leak
-static PAGINATION_CACHE: OnceLock<Mutex<LruCache<String, String>>> = OnceLock::new();
-
-fn get_cache() -> &'static Mutex<LruCache<String, String>> {
-    PAGINATION_CACHE.get_or_init(|| Mutex::new(LruCache::new(NonZeroUsize::new(1000).unwrap())))
-}
+use super::provider::{EmailProvider, CleanMessage, EmailAddress, MessageFormat, MessageList, MessageSummary, normalize_date, date_from_millis, AttachmentSummary, SendMessageRequest, ListParams, Label, BatchModifyRequest, Thread, AttachmentContent, Draft, ReplyMode, ReplyRequest, BatchItemResult, LabelColor, LabelRequest, SYSTEM_LABELS, dedup_quoted_text, format_addresses, partition_batch_ids};
+use super::address::{normalize_recipients, split_address_list};
+use super::mime::MimeBuilder;
+use super::pagination;
+use super::query::{SearchQuery, GMAIL_ARCHIVE_QUERY};
 
 pub struct GmailProvider {
     client: Client,
@@ -28,16 +20,94 @@ impl GmailProvider {
         Self { client }
     }
 
-    // Helper to fetch and parse a single message fully
+    // Fetch a message through its format=full structure: only the text/html bodies are
+    // downloaded, attachments are listed by partId and fetched later through get_attachment
     async fn fetch_and_parse_message(
         &self,
-        client: &Client,
         token: &str,
         id: &str,
     ) -> Result<CleanMessage, AppError> {
+        let data = self.fetch_full_resource(token, id).await?;
+        self.read_full_message(token, id, &data).await
+    }
+
+    // messages.get format=full: headers and the part tree, bodies only for small parts
+    async fn fetch_full_resource(&self, token: &str, id: &str) -> Result<serde_json::Value, AppError> {
+        let url = format!("https://gmail.googleapis.com/gmail/v1/users/me/messages/{}?format=full", id);
+
+        let res = self.client
+            .get(&url)
+            .bearer_auth(token)
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+            return Err(AppError::GmailApi(res.error_for_status().unwrap_err()));
+        }
+
+        Ok(res.json().await?)
+    }
+
+    // CleanMessage of a format=full resource, downloading the text/html bodies it does not inline
+    async fn read_full_message(&self, token: &str, id: &str, data: &serde_json::Value) -> Result<CleanMessage, AppError> {
+        let mut walk = PayloadWalk::default();
+        walk_payload(&data["payload"], &mut walk);
+
+        let (body_text, body_html) = tokio::try_join!(
+            self.fetch_text_part(token, id, walk.text),
+            self.fetch_text_part(token, id, walk.html),
+        )?;
+
+        Ok(parse_full_message(data, body_text, body_html, walk.attachments))
+    }
+
+    // Decoded body of a text part; bodies too large to inline come from attachments.get
+    async fn fetch_text_part(&self, token: &str, message_id: &str, part: Option<&serde_json::Value>) -> Result<Option<String>, AppError> {
+        let Some(part) = part else {
+            return Ok(None);
+        };
+        let bytes = self.fetch_part_data(token, message_id, part).await?;
+        // format=full hands out the part's bytes in its original charset
+        Ok(Some(decode_text(&bytes, part_charset(part).as_deref())))
+    }
+
+    // Bytes of a payload part: inlined in body.data for small parts, attachments.get otherwise
+    async fn fetch_part_data(&self, token: &str, message_id: &str, part: &serde_json::Value) -> Result<Vec<u8>, AppError> {
+        let encoded = if let Some(inline) = part["body"]["data"].as_str() {
+            inline.to_string()
+        } else if let Some(gmail_attachment_id) = part["body"]["attachmentId"].as_str() {
+            let url = format!(
+                "https://gmail.googleapis.com/gmail/v1/users/me/messages/{}/attachments/{}",
+                message_id, gmail_attachment_id
+            );
+
+            let res = self.client
+                .get(&url)
+                .bearer_auth(token)
+                .send()
+                .await?;
+
+            if !res.status().is_success() {
+                return Err(AppError::GmailApi(res.error_for_status().unwrap_err()));
+            }
+
+            let body: serde_json::Value = res.json().await?;
+            body["data"].as_str().unwrap_or_default().to_string()
+        } else {
+            // Empty part
+            String::new()
+        };
+
+        let bytes = URL_SAFE_NO_PAD.decode(encoded.trim_end_matches('='))
+            .map_err(|e| anyhow::anyhow!("Base64 Error: {}", e))?;
+        Ok(bytes)
+    }
+
+    // format=raw response (threadId, snippet, internalDate) together with the decoded MIME source
+    async fn fetch_raw_source(&self, token: &str, id: &str) -> Result<(serde_json::Value, Vec<u8>), AppError> {
         let url = format!("https://gmail.googleapis.com/gmail/v1/users/me/messages/{}?format=raw", id);
         
-        let res = client
+        let res = self.client
             .get(&url)
             .bearer_auth(token)
             .send()
@@ -54,53 +124,188 @@ impl GmailProvider {
         let sanitized_base64 = raw_base64.trim_end_matches('=');
         let raw_bytes = URL_SAFE_NO_PAD.decode(sanitized_base64).map_err(|e| anyhow::anyhow!("Base64 Error: {} (len: {})", e, raw_base64.len()))?;
 
+        Ok((data, raw_bytes))
+    }
+
+    // Opt-in (format=raw): download the whole MIME source and parse it locally
+    async fn fetch_and_parse_raw_message(
+        &self,
+        token: &str,
+        id: &str,
+    ) -> Result<CleanMessage, AppError> {
+        let (data, raw_bytes) = self.fetch_raw_source(token, id).await?;
+
         // Parse MIME
         let message = MessageParser::default().parse(&raw_bytes).ok_or_else(|| anyhow::anyhow!("Failed to parse email"))?;
 
         // Convert to Clean JSON
         let clean = CleanMessage {
             id: id.to_string(),
+            thread_id: data["threadId"].as_str().map(|s| s.to_string()),
             subject: message.subject().map(|s| s.to_string()),
-            from: message.from().map(|f| f.first().map(|a| a.name().unwrap_or(a.address().unwrap_or("Unknown"))).unwrap_or("Unknown").to_string()),
-            to: message.to().map(|t| t.iter().map(|a| a.address().unwrap_or("Unknown").to_string()).collect()),
-            cc: message.cc().map(|c| c.iter().map(|a| a.address().unwrap_or("Unknown").to_string()).collect()),
-            date: message.date().map(|d| d.to_rfc3339()),
+            from: message.from().and_then(|f| f.first()).map(|a| EmailAddress::from_parts(a.name(), a.address())),
+            to: message.to().map(|t| t.iter().map(|a| EmailAddress::from_parts(a.name(), a.address())).collect()),
+            cc: message.cc().map(|c| c.iter().map(|a| EmailAddress::from_parts(a.name(), a.address())).collect()),
+            // internalDate is when Gmail received the message, unlike the sender-controlled Date header
+            date: internal_date(&data).or_else(|| message.date().and_then(|d| normalize_date(&d.to_rfc3339()))),
             snippet: data["snippet"].as_str().unwrap_or("").to_string(),
             body_text: message.body_text(0).map(|b| b.to_string()),
             body_html: message.body_html(0).map(|b| b.to_string()),
-            attachments: message.attachments().map(|a| {
-                let filename = a.attachment_name()
-                    .or_else(|| a.content_type().and_then(|ct| ct.attribute("name")))
-                    .unwrap_or("unnamed")
-                    .to_string();
-                
-                let content_type = a.content_type()
-                    .map(|ct| format!("{}/{}", ct.c_type, ct.c_subtype.as_ref().unwrap_or(&"octet-stream".into())))
-                    .unwrap_or_else(|| "application/octet-stream".to_string());
-                
-                AttachmentSummary {
-                    filename,
-                    content_type,
-                    size: a.contents().len(),
-                    id: a.content_id().map(|id| id.to_string()),
-                }
-            }).collect(),
+            attachments: {
+                let part_ids = gmail_part_ids(&message);
+                message.attachments.iter().filter_map(|idx| message.parts.get(*idx).map(|a| (idx, a))).map(|(idx, a)| {
+                    let filename = a.attachment_name()
+                        .or_else(|| a.content_type().and_then(|ct| ct.attribute("name")))
+                        .unwrap_or("unnamed")
+                        .to_string();
+                    
+                    let content_type = a.content_type()
+                        .map(|ct| format!("{}/{}", ct.c_type, ct.c_subtype.as_ref().unwrap_or(&"octet-stream".into())))
+                        .unwrap_or_else(|| "application/octet-stream".to_string());
+                    
+                    AttachmentSummary {
+                        filename,
+                        content_type,
+                        size: a.contents().len(),
+                        id: part_ids.get(idx).cloned(),
+                        content_id: a.content_id().map(|id| id.to_string()),
+                    }
+                }).collect()
+            },
+            reply_text: None,
+            raw_date: message.header_raw("Date").map(|d| d.trim().to_string()),
+            raw_from: message.header_raw("From").map(|f| f.trim().to_string()),
         };
 
         Ok(clean)
     }
 
+    // The account's own address with the requested display name, when the message needs an explicit From
+    async fn sender(&self, token: &str, req: &SendMessageRequest) -> Result<Option<EmailAddress>, AppError> {
+        if req.from_name.is_none() && !req.request_read_receipt {
+            return Ok(None);
+        }
+        let profile = self.get_profile(token).await?;
+        Ok(Some(EmailAddress::from_parts(req.from_name.as_deref(), Some(&profile.email))))
+    }
+
+    async fn fetch_label(&self, token: &str, id: &str) -> Result<serde_json::Value, AppError> {
+        let url = format!("https://gmail.googleapis.com/gmail/v1/users/me/labels/{}", id);
+
+        let res = self.client
+            .get(&url)
+            .bearer_auth(token)
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+            return Err(AppError::GmailApi(res.error_for_status().unwrap_err()));
+        }
+
+        Ok(res.json().await?)
+    }
+
+    // Gmail nests labels by name ("Customers/Acme"), so a parent id becomes a name prefix
+    async fn nested_label_name(&self, token: &str, name: &str, parent_id: Option<&str>) -> Result<String, AppError> {
+        let Some(parent_id) = parent_id else {
+            return Ok(name.to_string());
+        };
+
+        let parent = self.fetch_label(token, parent_id).await?;
+        let parent_name = parent["name"].as_str().unwrap_or_default();
+        Ok(format!("{}/{}", parent_name, name))
+    }
+
+    // labels.patch, returning the updated label resource
+    async fn patch_label(&self, token: &str, id: &str, body: &serde_json::Value) -> Result<serde_json::Value, AppError> {
+        let url = format!("https://gmail.googleapis.com/gmail/v1/users/me/labels/{}", id);
+
+        let res = self.client
+            .patch(&url)
+            .bearer_auth(token)
+            .json(body)
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+            return Err(AppError::GmailApi(res.error_for_status().unwrap_err()));
+        }
+
+        Ok(res.json().await?)
+    }
+
+    // messages.modify on a single message
+    async fn modify_message_labels(&self, token: &str, id: &str, add: &[&str], remove: &[&str]) -> Result<(), AppError> {
+        let url = format!("https://gmail.googleapis.com/gmail/v1/users/me/messages/{}/modify", id);
+
+        let res = self.client
+            .post(&url)
+            .bearer_auth(token)
+            .json(&json!({
+                "addLabelIds": add,
+                "removeLabelIds": remove,
+            }))
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+            return Err(AppError::GmailApi(res.error_for_status().unwrap_err()));
+        }
+
+        Ok(())
+    }
+
+    // Bodyless call on a message resource (trash, untrash, delete)
+    async fn message_request(&self, token: &str, method: reqwest::Method, id: &str, suffix: &str) -> Result<(), AppError> {
+        let url = format!("https://gmail.googleapis.com/gmail/v1/users/me/messages/{}{}", id, suffix);
+
+        let res = self.client
+            .request(method, &url)
+            .bearer_auth(token)
+            .header("Content-Length", "0")
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+            return Err(AppError::GmailApi(res.error_for_status().unwrap_err()));
+        }
+
+        Ok(())
+    }
+
+    // Only the token of the page after `page_token`, used to jump to a page number
+    async fn fetch_next_page_token(
+        &self,
+        token: &str,
+        params: &ListParams,
+        page_token: Option<String>,
+    ) -> Result<Option<String>, AppError> {
+        let url = format!("{}&fields=nextPageToken", list_url(params, page_token.as_deref())?);
+
+        let res = self.client
+            .get(&url)
+            .bearer_auth(token)
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+            return Err(AppError::GmailApi(res.error_for_status().unwrap_err()));
+        }
+
+        let data: serde_json::Value = res.json().await?;
+        Ok(data["nextPageToken"].as_str().map(|s| s.to_string()))
+    }
+
     // Helper function to fetch metadata for a single message
     async fn fetch_message_metadata(
         &self,
         client: &Client,
         token: &str,
         id: &str,
-        thread_id: &str,
     ) -> Result<MessageSummary, AppError> {
         let url = format!(
-            "https://gmail.googleapis.com/gmail/v1/users/me/messages/{}?format=metadata&metadataHeaders=Subject&metadataHeaders=From&metadataHeaders=Date",
-            id
+            "https://gmail.googleapis.com/gmail/v1/users/me/messages/{}?format=metadata&{}",
+            id, SUMMARY_HEADERS
         );
         
         let res = client
@@ -114,51 +319,69 @@ impl GmailProvider {
         }
         
         let data: serde_json::Value = res.json().await?;
-        
-        // Parse headers
-        const EMPTY_ARRAY: &[serde_json::Value] = &[];
-        let headers = data["payload"]["headers"].as_array().map_or(EMPTY_ARRAY, |v| v.as_slice());
-        
-        let subject = headers
-            .iter()
-            .find(|h| h["name"].as_str() == Some("Subject"))
-            .and_then(|h| h["value"].as_str())
-            .map(|s| s.to_string());
-        
-        let from = headers
-            .iter()
-            .find(|h| h["name"].as_str() == Some("From"))
-            .and_then(|h| h["value"].as_str())
-            .map(|s| s.to_string());
-        
-        let date = headers
-            .iter()
-            .find(|h| h["name"].as_str() == Some("Date"))
-            .and_then(|h| h["value"].as_str())
-            .map(|s| s.to_string());
-        
-        // Check if unread (labelIds contains "UNREAD")
-        let unread = data["labelIds"]
-            .as_array()
-            .map(|labels| labels.iter().any(|l| l.as_str() == Some("UNREAD")))
-            .unwrap_or(false);
-        
-        // Check for attachments
-        let has_attachments = has_attachments_in_payload(&data["payload"]);
-        
-        let snippet = data["snippet"].as_str().unwrap_or("").to_string();
-        
-        Ok(MessageSummary {
-            id: id.to_string(),
-            thread_id: thread_id.to_string(),
-            snippet,
-            subject,
-            from,
-            date,
-            unread,
-            has_attachments,
-            messages_in_thread: None, // Not set for individual message fetch
-        })
+        Ok(parse_message_metadata(&data))
+    }
+
+    // Several GETs in one call to Gmail's batch endpoint; None for parts that did not succeed
+    async fn batch_get(&self, token: &str, paths: &[String]) -> Result<Vec<Option<serde_json::Value>>, AppError> {
+        const BOUNDARY: &str = "batch_gmail_api_proxy";
+
+        let mut body = String::new();
+        for (i, path) in paths.iter().enumerate() {
+            body.push_str(&format!(
+                "--{}\r\nContent-Type: application/http\r\nContent-ID: <item-{}>\r\n\r\nGET {}\r\n\r\n",
+                BOUNDARY, i, path
+            ));
+        }
+        body.push_str(&format!("--{}--\r\n", BOUNDARY));
+
+        let res = self.client
+            .post("https://gmail.googleapis.com/batch/gmail/v1")
+            .bearer_auth(token)
+            .header("Content-Type", format!("multipart/mixed; boundary={}", BOUNDARY))
+            .body(body)
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+            return Err(AppError::GmailApi(res.error_for_status().unwrap_err()));
+        }
+
+        // The response uses its own boundary
+        let boundary = res.headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|ct| ct.split(';').find_map(|p| p.trim().strip_prefix("boundary=")))
+            .map(|b| b.trim_matches('"').to_string())
+            .ok_or_else(|| anyhow::anyhow!("Gmail batch response without boundary"))?;
+
+        let text = res.text().await?;
+        Ok(parse_batch_response(&text, &boundary, paths.len()))
+    }
+
+    // Summary of a whole thread: its latest message plus count and participants
+    async fn fetch_thread_summary(
+        &self,
+        token: &str,
+        thread_id: &str,
+    ) -> Result<MessageSummary, AppError> {
+        let url = format!(
+            "https://gmail.googleapis.com/gmail/v1/users/me/threads/{}?format=metadata&{}",
+            thread_id, SUMMARY_HEADERS
+        );
+
+        let res = self.client
+            .get(&url)
+            .bearer_auth(token)
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+            return Err(AppError::GmailApi(res.error_for_status().unwrap_err()));
+        }
+
+        let data: serde_json::Value = res.json().await?;
+        summarize_thread(thread_id, &data)
     }
 }
 
@@ -168,76 +391,24 @@ impl EmailProvider for GmailProvider {
         &self,
         token: &str,
         params: ListParams,
-    ) -> Result<serde_json::Value, AppError> {
+    ) -> Result<MessageList, AppError> {
         let client = &self.client; // Fixed Point 14
 
-        let mut url = "https://gmail.googleapis.com/gmail/v1/users/me/messages".to_string();
-        
-        // Pagination Logic
-        let page_num = params.page_number.unwrap_or(1);
-        
-        // Determine the actual Gmail Token to use
-        let actual_token = if page_num <= 1 {
-            // Page 1 always has no token
-            None 
-        } else if let Some(manual_token) = &params.page_token {
-            // User provided explicit token (overrides page number)
-            Some(manual_token.clone())
-        } else {
-            // Look up token for this page number in Cache
-            // Create a unique cache key for this user's specific query
-            let cache_key_prefix = format!(
-                "{}_{}_{}_{}",
-                simple_hash(token),
-                params.q.as_deref().unwrap_or(""),
-                params.label_ids.as_deref().unwrap_or(""),
-                params.max_results.unwrap_or(10)
-            );
-            let key = format!("{}_{}", cache_key_prefix, page_num);
-            
-            // Try to get token from cache
-            // Fixed Point 17: Safe lock
-            let mut cache = get_cache().lock().unwrap_or_else(|e| e.into_inner());
-            match cache.get(&key) {
-                Some(t) => Some(t.clone()),
-                None => {
-                    if page_num > 1 {
-                         return Ok(json!({
-                            "messages": [],
-                            "resultSizeEstimate": 0,
-                            "warning": "Page token not found. Please navigate sequentially from Page 1."
-                        }));
-                    }
-                    None
-                }
-            }
+        // Pagination Logic: resolve our cursor / page number into a Gmail page token
+        let resolved = pagination::resolve("gmail", &params, |page_token| {
+            self.fetch_next_page_token(token, &params, page_token)
+        }).await?;
+        let Some(resolved) = resolved else {
+            return Ok(pagination::not_found(&params));
         };
+        let page_num = resolved.page;
 
-        // Build query params
-        let mut query = Vec::new();
-        if let Some(max) = params.max_results {
-            query.push(format!("maxResults={}", max));
-        }
-        if let Some(q) = &params.q {
-            query.push(format!("q={}", urlencoding::encode(q)));
-        }
-        
-        // Use the resolved token
-        if let Some(t) = actual_token {
-            query.push(format!("pageToken={}", t));
-        }
-        
-        if let Some(labels) = &params.label_ids {
-            for label in labels.split(',') {
-                query.push(format!("labelIds={}", label.trim()));
-            }
-        }
-
-        if !query.is_empty() {
-            url = format!("{}?{}", url, query.join("&"));
-        }
+        // With collapse_threads Gmail pages over threads (threads.list) instead of messages,
+        // so counts and ordering hold across pages
+        let collapse = params.collapse_threads.unwrap_or(false);
+        let url = list_url(&params, resolved.upstream_token.as_deref())?;
 
-        // Get list of message IDs
+        // Get list of message (or thread) IDs
         let res = client
             .get(&url)
             .bearer_auth(token)
@@ -250,62 +421,64 @@ impl EmailProvider for GmailProvider {
 
         let list_response: serde_json::Value = res.json().await?;
         
-        // CACHE UPDATE: Save the 'nextPageToken' for the NEXT page (current + 1)
-        if let Some(next_token) = list_response["nextPageToken"].as_str() {
-            let cache_key_prefix = format!(
-                "{}_{}_{}_{}",
-                simple_hash(token),
-                params.q.as_deref().unwrap_or(""),
-                params.label_ids.as_deref().unwrap_or(""),
-                params.max_results.unwrap_or(10)
-            );
-            let next_page_num = page_num + 1;
-            let key = format!("{}_{}", cache_key_prefix, next_page_num);
-            
-            let mut cache = get_cache().lock().unwrap_or_else(|e| e.into_inner());
-            cache.put(key, next_token.to_string());
-        }
+        // Hand out the next Gmail token wrapped in a cursor for page (current + 1)
+        let next_page_token = list_response["nextPageToken"]
+            .as_str()
+            .map(|t| pagination::encode("gmail", &params, page_num + 1, t))
+            .transpose()?;
         
         // Extract message IDs
-        let messages_raw = list_response["messages"]
+        let items_raw = list_response[if collapse { "threads" } else { "messages" }]
             .as_array()
             .map(|arr| arr.to_vec())
             .unwrap_or_default();
         
-        if messages_raw.is_empty() {
-            return Ok(json!({
-                "messages": [],
-                "nextPageToken": list_response["nextPageToken"],
-                "page": page_num,
-                "resultSizeEstimate": 0
-            }));
+        if items_raw.is_empty() {
+            return Ok(MessageList::new(Vec::new(), page_num, next_page_token, Some(0)));
         }
 
-        // Fetch metadata for each message with limited concurrency to avoid 429 errors
         use futures::stream::{self, StreamExt};
-        
-        // Concurrency limit for Gmail API requests
+
+        // Gmail allows up to 100 calls per batch but recommends no more than 50
+        const BATCH_LIMIT: usize = 50;
+        // Concurrency limit for the single-call fallback
         const CONCURRENCY_LIMIT: usize = 5;
 
-        let results = stream::iter(messages_raw)
-            .map(|msg| {
-                let id = msg["id"].as_str().unwrap_or("").to_string();
-                let thread_id = msg["threadId"].as_str().unwrap_or("").to_string();
-                let client = client.clone();
-                let token = token.to_string();
-                let provider = GmailProvider::new(client.clone());
+        let resource = if collapse { "threads" } else { "messages" };
+        let ids: Vec<String> = items_raw.iter()
+            .map(|item| item["id"].as_str().unwrap_or("").to_string())
+            .collect();
+
+        let mut fetched: Vec<Option<serde_json::Value>> = Vec::with_capacity(ids.len());
+        for chunk in ids.chunks(BATCH_LIMIT) {
+            let paths: Vec<String> = chunk.iter()
+                .map(|id| format!("/gmail/v1/users/me/{}/{}?format=metadata&{}", resource, id, SUMMARY_HEADERS))
+                .collect();
+
+            match self.batch_get(token, &paths).await {
+                Ok(parts) => fetched.extend(parts),
+                Err(e) => {
+                    tracing::warn!("Gmail batch request failed, falling back to single calls: {:?}", e);
+                    fetched.extend(std::iter::repeat_n(None, chunk.len()));
+                }
+            }
+        }
 
-                async move {
-                    provider.fetch_message_metadata(&client, &token, &id, &thread_id).await
+        // Parts the batch could not serve (e.g. rate limited inside the batch) are fetched one by one.
+        // `buffered` keeps Gmail's ordering (newest first)
+        let results = stream::iter(ids.into_iter().zip(fetched))
+            .map(|(id, data)| async move {
+                match (data, collapse) {
+                    (Some(data), true) => summarize_thread(&id, &data),
+                    (Some(data), false) => Ok(parse_message_metadata(&data)),
+                    (None, true) => self.fetch_thread_summary(token, &id).await,
+                    (None, false) => self.fetch_message_metadata(client, token, &id).await,
                 }
             })
-            .buffer_unordered(CONCURRENCY_LIMIT)
+            .buffered(CONCURRENCY_LIMIT)
             .collect::<Vec<_>>()
             .await;
         
-        // Since we are not using JoinHandles anymore but direct futures, we don't need to unwrap blocking join errors
-        // The results are Vec<Result<MessageSummary, AppError>> directly.
-        
         let enriched_messages: Vec<MessageSummary> = results
             .into_iter()
             .filter_map(|r| {
@@ -319,117 +492,86 @@ impl EmailProvider for GmailProvider {
             })
             .collect();
 
-        let mut enriched_messages = enriched_messages;
-
-        // If collapse_threads is enabled, group by thread_id and keep only the latest message
-        if params.collapse_threads.unwrap_or(false) {
-            use std::collections::HashMap;
-            
-            let mut threads: HashMap<String, Vec<MessageSummary>> = HashMap::new();
-            
-            // Group messages by thread_id
-            for msg in enriched_messages {
-                threads.entry(msg.thread_id.clone())
-                    .or_insert_with(Vec::new)
-                    .push(msg);
-            }
-            
-            // For each thread, keep only the latest message and add count
-            enriched_messages = threads
-                .into_iter()
-                .map(|(_thread_id, mut msgs)| {
-                    let count = msgs.len() as u32;
-                    // Sort by date (newest first) - use date string comparison as fallback
-                    msgs.sort_by(|a, b| b.date.cmp(&a.date));
-                    
-                    // Take the latest message and add thread count
-                    let mut latest = msgs.into_iter().next().unwrap();
-                    latest.messages_in_thread = Some(count);
-                    latest
-                })
-                .collect();
-            
-            // Sort final results by date (newest first)
-            enriched_messages.sort_by(|a, b| b.date.cmp(&a.date));
-        }
-
-        Ok(json!({
-            "messages": enriched_messages,
-            "nextPageToken": list_response["nextPageToken"],
-            "page": page_num,
-            "next_page": page_num + 1,
-            "resultSizeEstimate": list_response["resultSizeEstimate"]
-        }))
+        Ok(MessageList::new(enriched_messages, page_num, next_page_token, list_response["resultSizeEstimate"].as_u64()))
     }
 
-    async fn get_message(&self, token: &str, id: &str) -> Result<CleanMessage, AppError> {
-        self.fetch_and_parse_message(&self.client, token, id).await
+    async fn get_message(&self, token: &str, id: &str, format: MessageFormat) -> Result<CleanMessage, AppError> {
+        match format {
+            MessageFormat::Full => self.fetch_and_parse_message(token, id).await,
+            MessageFormat::Raw => self.fetch_and_parse_raw_message(token, id).await,
+        }
     }
 
-    async fn send_message(&self, token: &str, req: SendMessageRequest) -> Result<serde_json::Value, AppError> {
-        let to_list: Vec<String> = req.to.iter()
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty() && s.contains('@'))
-            .collect();
-        let to_header = to_list.join(", ");
-        
-        let cc_list: Vec<String> = req.cc.unwrap_or_default().iter()
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty() && s.contains('@'))
-            .collect();
-        let cc_header = cc_list.join(", ");
+    async fn get_thread(&self, token: &str, thread_id: &str) -> Result<Thread, AppError> {
+        let url = format!("https://gmail.googleapis.com/gmail/v1/users/me/threads/{}?format=minimal", thread_id);
 
-        // Fixed Point 15: Unique boundary
-        let boundary = format!("boundary_{}", uuid::Uuid::new_v4()); 
+        let res = self.client
+            .get(&url)
+            .bearer_auth(token)
+            .send()
+            .await?;
 
-        let mut email_content = String::new();
-        if !to_header.is_empty() {
-            email_content.push_str(&format!("To: {}\r\n", to_header));
-        }
-        if !cc_header.is_empty() {
-            email_content.push_str(&format!("Cc: {}\r\n", cc_header));
+        if !res.status().is_success() {
+            return Err(AppError::GmailApi(res.error_for_status().unwrap_err()));
         }
-        email_content.push_str(&format!("Subject: {}\r\n", req.subject));
-        
-        let _has_attachments = req.attachments.as_ref().map_or(false, |atts| !atts.is_empty());
 
-        // Always use multipart/mixed for consistency and correct rendering
-        email_content.push_str("MIME-Version: 1.0\r\n");
-        email_content.push_str(&format!("Content-Type: multipart/mixed; boundary=\"{}\"\r\n\r\n", boundary));
-        
-        tracing::info!("Sending Gmail: To='{}', Cc='{}', Subject='{}'", to_header, cc_header, req.subject);
-
-        // HTML Part
-        use base64::{Engine as _, engine::general_purpose::STANDARD};
-        email_content.push_str(&format!("--{}\r\n", boundary));
-        email_content.push_str("Content-Type: text/html; charset=utf-8\r\n");
-        email_content.push_str("Content-Transfer-Encoding: base64\r\n");
-        email_content.push_str("Content-Disposition: inline\r\n\r\n");
-        email_content.push_str(&STANDARD.encode(req.body.as_bytes()));
-        email_content.push_str("\r\n\r\n");
-
-        // Attachments
-        if let Some(attachments) = &req.attachments {
-            for att in attachments {
-                email_content.push_str(&format!("--{}\r\n", boundary));
-                email_content.push_str(&format!("Content-Type: {}; name=\"{}\"\r\n", att.mime_type, att.filename));
-                email_content.push_str(&format!("Content-Disposition: attachment; filename=\"{}\"\r\n", att.filename));
-                email_content.push_str("Content-Transfer-Encoding: base64\r\n\r\n");
-                
-                let encoded = STANDARD.encode(&att.content);
-                email_content.push_str(&encoded);
-                email_content.push_str("\r\n\r\n");
-            }
-        }
-        
-        // End Boundary
-        email_content.push_str(&format!("--{}--", boundary));
+        let data: serde_json::Value = res.json().await?;
 
-        let raw_encoded = URL_SAFE_NO_PAD.encode(email_content.as_bytes());
+        // threads.get returns messages in chronological order; `buffered` keeps that order
+        let ids: Vec<String> = data["messages"]
+            .as_array()
+            .map(|arr| arr.iter().filter_map(|m| m["id"].as_str().map(|s| s.to_string())).collect())
+            .unwrap_or_default();
 
-        let body = json!({
+        use futures::stream::{self, StreamExt, TryStreamExt};
+        const CONCURRENCY_LIMIT: usize = 5;
+
+        let mut messages: Vec<CleanMessage> = stream::iter(ids)
+            .map(|id| async move { self.fetch_and_parse_message(token, &id).await })
+            .buffered(CONCURRENCY_LIMIT)
+            .try_collect()
+            .await?;
+
+        dedup_quoted_text(&mut messages);
+
+        Ok(Thread {
+            id: thread_id.to_string(),
+            messages,
+        })
+    }
+
+    async fn get_attachment(&self, token: &str, message_id: &str, attachment_id: &str) -> Result<AttachmentContent, AppError> {
+        // Our attachment ids are Gmail partIds; resolve them to the current attachmentId
+        let data = self.fetch_full_resource(token, message_id).await?;
+        let part = find_part(&data["payload"], attachment_id)
+            .ok_or_else(|| AppError::NotFound(format!("Attachment {} not found in message {}", attachment_id, message_id)))?;
+
+        let filename = part["filename"].as_str().filter(|f| !f.is_empty()).unwrap_or("unnamed").to_string();
+        let mime_type = part["mimeType"].as_str().unwrap_or("application/octet-stream").to_string();
+
+        let bytes = self.fetch_part_data(token, message_id, part).await?;
+
+        Ok(AttachmentContent {
+            filename,
+            mime_type,
+            size: Some(bytes.len() as u64),
+            body: axum::body::Body::from(bytes),
+        })
+    }
+
+    async fn send_message(&self, token: &str, req: SendMessageRequest) -> Result<serde_json::Value, AppError> {
+        tracing::info!("Sending Gmail: To='{}', Cc='{}', Subject='{}'", req.to.join(", "), req.cc.as_deref().unwrap_or_default().join(", "), req.subject);
+
+        let sender = self.sender(token, &req).await?;
+        let raw_encoded = build_raw_message(&req, sender.as_ref());
+
+        let mut body = json!({
             "raw": raw_encoded
         });
+        // Gmail only threads the message if threadId is set and the headers/subject match
+        if let Some(thread_id) = req.thread_id.as_deref().filter(|t| !t.is_empty()) {
+            body["threadId"] = json!(thread_id);
+        }
 
         let res = self.client
             .post("https://gmail.googleapis.com/gmail/v1/users/me/messages/send")
@@ -449,45 +591,179 @@ impl EmailProvider for GmailProvider {
         Ok(json)
     }
 
-    async fn list_labels(&self, token: &str) -> Result<Vec<Label>, AppError> {
-        let url = "https://gmail.googleapis.com/gmail/v1/users/me/labels";
+    async fn reply(&self, token: &str, message_id: &str, mode: ReplyMode, req: ReplyRequest) -> Result<serde_json::Value, AppError> {
+        // One format=full fetch serves the headers, the quoted body and the forwarded attachments
+        let data = self.fetch_full_resource(token, message_id).await?;
+        let original = self.read_full_message(token, message_id, &data).await?;
+        let header = |name: &str| payload_header(&data["payload"], name);
 
-        let res = self.client
-            .get(url)
-            .bearer_auth(token)
-            .send()
-            .await?;
+        let original_from = header("From").unwrap_or_default();
+        let original_subject = header("Subject").unwrap_or_default();
+
+        // The own address is only needed to leave it out of computed reply recipients
+        let me = match mode {
+            ReplyMode::Forward => String::new(),
+            ReplyMode::Reply | ReplyMode::ReplyAll => self.get_profile(token).await?.email,
+        };
+        let (to, cc) = reply_recipients(mode, &header, &me, &req)?;
+
+        let original_date = header("Date").unwrap_or_default();
+        let original_body = original.body_html.clone().unwrap_or_else(|| {
+            html_escape::encode_text(original.body_text.as_deref().unwrap_or("")).replace('\n', "<br>")
+        });
+
+        let (subject, body) = if mode == ReplyMode::Forward {
+            let quote = format!(
+                "<br><div class=\"gmail_quote\"><div class=\"gmail_attr\">---------- Forwarded message ---------<br>From: {}<br>Date: {}<br>Subject: {}<br>To: {}<br></div><br>{}</div>",
+                html_escape::encode_text(&original_from),
+                html_escape::encode_text(&original_date),
+                html_escape::encode_text(&original_subject),
+                html_escape::encode_text(&header("To").unwrap_or_default()),
+                original_body
+            );
+            (prefixed_subject(&original_subject, "Fwd:"), format!("{}{}", req.body, quote))
+        } else {
+            let quote = format!(
+                "<br><div class=\"gmail_quote\"><div class=\"gmail_attr\">On {}, {} wrote:<br></div><blockquote class=\"gmail_quote\" style=\"margin:0px 0px 0px 0.8ex;border-left:1px solid rgb(204,204,204);padding-left:1ex\">{}</blockquote></div>",
+                html_escape::encode_text(&original_date),
+                html_escape::encode_text(&original_from),
+                original_body
+            );
+            (prefixed_subject(&original_subject, "Re:"), format!("{}{}", req.body, quote))
+        };
+
+        let mut attachments = req.attachments.unwrap_or_default();
+        if mode == ReplyMode::Forward {
+            // A forward carries the original attachments along. Their parts are already known
+            // from the fetch above; Gmail would reject a message over its size limit anyway.
+            let mut total: usize = attachments.iter().map(|a| a.content.len()).sum();
+            for summary in &original.attachments {
+                let Some(part) = summary.id.as_deref().and_then(|id| find_part(&data["payload"], id)) else {
+                    continue;
+                };
+                total += summary.size;
+                if total > MAX_FORWARD_SIZE {
+                    return Err(AppError::BadRequest(format!("Attachments of a forward are limited to {} MB", MAX_FORWARD_SIZE / (1024 * 1024))));
+                }
+                attachments.push(super::provider::Attachment {
+                    filename: summary.filename.clone(),
+                    content: self.fetch_part_data(token, message_id, part).await?,
+                    mime_type: summary.content_type.clone(),
+                    // Keeps cid: references in the quoted original working
+                    content_id: summary.content_id.clone(),
+                });
+            }
+        }
+
+        let message_id_header = header("Message-ID");
+        let references = match (header("References"), message_id_header.as_deref()) {
+            (Some(refs), Some(mid)) => Some(format!("{} {}", refs, mid)),
+            (None, Some(mid)) => Some(mid.to_string()),
+            (refs, None) => refs,
+        };
+
+        let send_req = SendMessageRequest {
+            to,
+            cc: Some(cc),
+            subject,
+            body,
+            thread_id: data["threadId"].as_str().map(|s| s.to_string()),
+            attachments: Some(attachments),
+            in_reply_to: message_id_header,
+            references,
+            ..Default::default()
+        };
+
+        self.send_message(token, send_req).await
+    }
+
+    async fn list_drafts(&self, token: &str, max_results: Option<u32>) -> Result<Vec<Draft>, AppError> {
+        let url = format!(
+            "https://gmail.googleapis.com/gmail/v1/users/me/drafts?maxResults={}",
+            max_results.unwrap_or(10)
+        );
+
+        let res = self.client
+            .get(&url)
+            .bearer_auth(token)
+            .send()
+            .await?;
 
         if !res.status().is_success() {
             return Err(AppError::GmailApi(res.error_for_status().unwrap_err()));
         }
 
         let data: serde_json::Value = res.json().await?;
-        let labels_raw = data["labels"].as_array().ok_or_else(|| anyhow::anyhow!("Labels not found"))?;
+        let ids: Vec<String> = data["drafts"]
+            .as_array()
+            .map(|arr| arr.iter().filter_map(|d| d["id"].as_str().map(|s| s.to_string())).collect())
+            .unwrap_or_default();
+
+        // drafts.list only returns ids, fetch headers for each (same concurrency as list_messages)
+        use futures::stream::{self, StreamExt};
+        const CONCURRENCY_LIMIT: usize = 5;
 
-        let labels = labels_raw
-            .iter()
-            .map(|l| Label {
-                id: l["id"].as_str().unwrap_or("").to_string(),
-                name: l["name"].as_str().unwrap_or("").to_string(),
-                label_type: l["type"].as_str().map(|s| s.to_string()),
+        let results = stream::iter(ids)
+            .map(|id| async move {
+                let url = format!(
+                    "https://gmail.googleapis.com/gmail/v1/users/me/drafts/{}?format=metadata&metadataHeaders=Subject&metadataHeaders=To&metadataHeaders=Date",
+                    id
+                );
+                let res = self.client.get(&url).bearer_auth(token).send().await?;
+                if !res.status().is_success() {
+                    return Err(AppError::GmailApi(res.error_for_status().unwrap_err()));
+                }
+                let data: serde_json::Value = res.json().await?;
+                Ok(parse_draft(&data))
             })
-            .collect();
+            .buffered(CONCURRENCY_LIMIT)
+            .collect::<Vec<Result<Draft, AppError>>>()
+            .await;
 
-        Ok(labels)
+        Ok(results
+            .into_iter()
+            .filter_map(|r| match r {
+                Ok(d) => Some(d),
+                Err(e) => {
+                    tracing::error!("Failed to fetch draft metadata: {:?}", e);
+                    None
+                }
+            })
+            .collect())
     }
 
-    async fn batch_modify_labels(&self, token: &str, req: BatchModifyRequest) -> Result<(), AppError> {
-        let url = "https://gmail.googleapis.com/gmail/v1/users/me/messages/batchModify";
+    async fn create_draft(&self, token: &str, req: SendMessageRequest) -> Result<Draft, AppError> {
+        let sender = self.sender(token, &req).await?;
+        let body = json!({ "message": draft_message_body(&req, sender.as_ref()) });
 
+        let res = self.client
+            .post("https://gmail.googleapis.com/gmail/v1/users/me/drafts")
+            .bearer_auth(token)
+            .json(&body)
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+            let status = res.status();
+            let error_text = res.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+            tracing::error!("Gmail API create draft error ({}): {}", status, error_text);
+            return Err(AppError::BadGateway(format!("Gmail API Error {}: {}", status, error_text)));
+        }
+
+        let data: serde_json::Value = res.json().await?;
+        Ok(parse_draft(&data))
+    }
+
+    async fn update_draft(&self, token: &str, draft_id: &str, req: SendMessageRequest) -> Result<Draft, AppError> {
+        let url = format!("https://gmail.googleapis.com/gmail/v1/users/me/drafts/{}", draft_id);
+        let sender = self.sender(token, &req).await?;
         let body = json!({
-            "ids": req.ids,
-            "addLabelIds": req.add_label_ids.unwrap_or_default(),
-            "removeLabelIds": req.remove_label_ids.unwrap_or_default(),
+            "id": draft_id,
+            "message": draft_message_body(&req, sender.as_ref())
         });
 
         let res = self.client
-            .post(url)
+            .put(&url)
             .bearer_auth(token)
             .json(&body)
             .send()
@@ -495,14 +771,295 @@ impl EmailProvider for GmailProvider {
 
         if !res.status().is_success() {
             let status = res.status();
-            let text = res.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-            tracing::error!("Gmail API batch_modify error: {} - {}", status, text);
-            return Err(AppError::BadGateway(format!("Gmail API Error {}: {}", status, text)));
+            let error_text = res.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+            tracing::error!("Gmail API update draft error ({}): {}", status, error_text);
+            return Err(AppError::BadGateway(format!("Gmail API Error {}: {}", status, error_text)));
+        }
+
+        let data: serde_json::Value = res.json().await?;
+        Ok(parse_draft(&data))
+    }
+
+    async fn send_draft(&self, token: &str, draft_id: &str) -> Result<serde_json::Value, AppError> {
+        let res = self.client
+            .post("https://gmail.googleapis.com/gmail/v1/users/me/drafts/send")
+            .bearer_auth(token)
+            .json(&json!({ "id": draft_id }))
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+            let status = res.status();
+            let error_text = res.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+            tracing::error!("Gmail API send draft error ({}): {}", status, error_text);
+            return Err(AppError::BadGateway(format!("Gmail API Error {}: {}", status, error_text)));
+        }
+
+        let json: serde_json::Value = res.json().await?;
+        Ok(json)
+    }
+
+    async fn mark_read(&self, token: &str, id: &str, read: bool) -> Result<(), AppError> {
+        if read {
+            self.modify_message_labels(token, id, &[], &["UNREAD"]).await
+        } else {
+            self.modify_message_labels(token, id, &["UNREAD"], &[]).await
+        }
+    }
+
+    async fn set_starred(&self, token: &str, id: &str, starred: bool) -> Result<(), AppError> {
+        if starred {
+            self.modify_message_labels(token, id, &["STARRED"], &[]).await
+        } else {
+            self.modify_message_labels(token, id, &[], &["STARRED"]).await
+        }
+    }
+
+    async fn archive(&self, token: &str, id: &str) -> Result<(), AppError> {
+        // Archiving in Gmail means dropping the INBOX label
+        self.modify_message_labels(token, id, &[], &["INBOX"]).await
+    }
+
+    async fn trash(&self, token: &str, id: &str) -> Result<(), AppError> {
+        self.message_request(token, reqwest::Method::POST, id, "/trash").await
+    }
+
+    async fn restore(&self, token: &str, id: &str) -> Result<(), AppError> {
+        self.message_request(token, reqwest::Method::POST, id, "/untrash").await
+    }
+
+    async fn delete_message(&self, token: &str, id: &str) -> Result<(), AppError> {
+        self.message_request(token, reqwest::Method::DELETE, id, "").await
+    }
+
+    async fn list_labels(&self, token: &str, with_counts: bool) -> Result<Vec<Label>, AppError> {
+        let url = "https://gmail.googleapis.com/gmail/v1/users/me/labels";
+
+        let res = self.client
+            .get(url)
+            .bearer_auth(token)
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+            return Err(AppError::GmailApi(res.error_for_status().unwrap_err()));
+        }
+
+        let data: serde_json::Value = res.json().await?;
+        let labels_raw = data["labels"].as_array().ok_or_else(|| anyhow::anyhow!("Labels not found"))?;
+
+        // labels.list has no counts, only labels.get does. A label whose labels.get fails is
+        // still listed, just without counts.
+        let mut labels: Vec<Label> = if with_counts {
+            const CONCURRENCY_LIMIT: usize = 5;
+            use futures::stream::{self, StreamExt};
+
+            stream::iter(labels_raw.iter().cloned())
+                .map(|l| async move {
+                    let Some(id) = l["id"].as_str() else {
+                        return parse_label(&l);
+                    };
+                    match self.fetch_label(token, id).await {
+                        Ok(details) => parse_label(&details),
+                        Err(e) => {
+                            tracing::warn!("Failed to fetch counts of label {}: {:?}", id, e);
+                            parse_label(&l)
+                        }
+                    }
+                })
+                .buffered(CONCURRENCY_LIMIT)
+                .collect()
+                .await
+        } else {
+            labels_raw.iter().map(parse_label).collect()
+        };
+
+        // Gmail nests by name, so "Customers/Acme" hangs under the label named "Customers"
+        let by_name: HashMap<String, String> = labels.iter().map(|l| (l.name.clone(), l.id.clone())).collect();
+        for label in &mut labels {
+            if let Some((parent, _)) = label.name.rsplit_once('/') {
+                label.parent_id = by_name.get(parent).cloned();
+            }
+        }
+
+        labels.push(Label::virtual_system("ARCHIVE", "Archive"));
+
+        Ok(labels)
+    }
+
+    async fn create_label(&self, token: &str, req: LabelRequest) -> Result<Label, AppError> {
+        let name = req.name.as_deref().map(str::trim).unwrap_or_default();
+        if name.is_empty() {
+            return Err(AppError::BadRequest("Label name is required".to_string()));
+        }
+
+        let mut body = json!({
+            "name": self.nested_label_name(token, name, req.parent_id.as_deref()).await?,
+            "labelListVisibility": "labelShow",
+            "messageListVisibility": "show",
+        });
+        if let Some(color) = &req.color {
+            body["color"] = label_color_json(color);
+        }
+
+        let res = self.client
+            .post("https://gmail.googleapis.com/gmail/v1/users/me/labels")
+            .bearer_auth(token)
+            .json(&body)
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+            return Err(AppError::GmailApi(res.error_for_status().unwrap_err()));
+        }
+
+        let data: serde_json::Value = res.json().await?;
+        Ok(parse_label(&data))
+    }
+
+    async fn update_label(&self, token: &str, label_id: &str, req: LabelRequest) -> Result<Label, AppError> {
+        let mut body = json!({});
+        let mut renamed = None;
+        if let Some(name) = req.name.as_deref().map(str::trim).filter(|n| !n.is_empty()) {
+            let current = self.fetch_label(token, label_id).await?;
+            let old_name = current["name"].as_str().unwrap_or_default().to_string();
+            let new_name = match req.parent_id.as_deref() {
+                Some(parent_id) => self.nested_label_name(token, name, Some(parent_id)).await?,
+                // Without parent_id the label stays under its current parent
+                None => sibling_label_name(&old_name, name),
+            };
+            body["name"] = json!(new_name);
+            renamed = Some((old_name, new_name));
+        } else if req.parent_id.is_some() {
+            return Err(AppError::BadRequest("Moving a Gmail label requires its name".to_string()));
+        }
+        if let Some(color) = &req.color {
+            body["color"] = label_color_json(color);
+        }
+
+        let data = self.patch_label(token, label_id, &body).await?;
+
+        // Gmail nests by name, so the children follow their parent to the new name
+        if let Some((old_name, new_name)) = renamed.filter(|(old, new)| old != new) {
+            let res = self.client
+                .get("https://gmail.googleapis.com/gmail/v1/users/me/labels")
+                .bearer_auth(token)
+                .send()
+                .await?;
+
+            if !res.status().is_success() {
+                return Err(AppError::GmailApi(res.error_for_status().unwrap_err()));
+            }
+
+            let labels: serde_json::Value = res.json().await?;
+            for (id, name) in child_label_renames(labels["labels"].as_array().map_or(&[], |v| v.as_slice()), &old_name, &new_name) {
+                self.patch_label(token, &id, &json!({ "name": name })).await?;
+            }
+        }
+
+        Ok(parse_label(&data))
+    }
+
+    async fn delete_label(&self, token: &str, label_id: &str) -> Result<(), AppError> {
+        let url = format!("https://gmail.googleapis.com/gmail/v1/users/me/labels/{}", label_id);
+
+        let res = self.client
+            .delete(&url)
+            .bearer_auth(token)
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+            return Err(AppError::GmailApi(res.error_for_status().unwrap_err()));
         }
 
         Ok(())
     }
 
+    async fn batch_modify_labels(&self, token: &str, req: BatchModifyRequest) -> Result<Vec<BatchItemResult>, AppError> {
+        // batchModify rejects more than 1000 ids per call
+        const BATCH_MODIFY_LIMIT: usize = 1000;
+        let url = "https://gmail.googleapis.com/gmail/v1/users/me/messages/batchModify";
+        let (add_label_ids, remove_label_ids) = gmail_label_changes(
+            req.add_label_ids.unwrap_or_default(),
+            req.remove_label_ids.unwrap_or_default(),
+        );
+
+        let (ids, mut results) = partition_batch_ids(req.ids);
+
+        for chunk in ids.chunks(BATCH_MODIFY_LIMIT) {
+            let body = json!({
+                "ids": chunk,
+                "addLabelIds": add_label_ids,
+                "removeLabelIds": remove_label_ids,
+            });
+
+            let res = self.client
+                .post(url)
+                .bearer_auth(token)
+                .json(&body)
+                .send()
+                .await;
+
+            // batchModify is all-or-nothing per call, so a failure applies to the whole chunk
+            let failure = match res {
+                Ok(res) if res.status().is_success() => None,
+                Ok(res) => {
+                    let status = res.status();
+                    let text = res.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+                    tracing::error!("Gmail API batch_modify error: {} - {}", status, text);
+                    Some(format!("Gmail API Error {}: {}", status, text))
+                }
+                Err(e) => {
+                    tracing::error!("Gmail API batch_modify reqwest error: {}", e);
+                    Some(e.to_string())
+                }
+            };
+
+            results.extend(chunk.iter().cloned().map(|id| match &failure {
+                None => BatchItemResult::succeeded(id),
+                Some(reason) => BatchItemResult::failed(id, reason.clone()),
+            }));
+        }
+
+        Ok(results)
+    }
+
+    async fn get_raw_message(&self, token: &str, id: &str) -> Result<Vec<u8>, AppError> {
+        let (_, raw) = self.fetch_raw_source(token, id).await?;
+        Ok(raw)
+    }
+
+    async fn import_message(&self, token: &str, raw: Vec<u8>, label_id: &str) -> Result<serde_json::Value, AppError> {
+        // Archived mail in Gmail is simply mail without INBOX, so ARCHIVE imports with no labels
+        let label_ids: Vec<String> = match label_id.to_uppercase().as_str() {
+            "ARCHIVE" => Vec::new(),
+            system if SYSTEM_LABELS.contains(&system) => vec![system.to_string()],
+            _ => vec![label_id.to_string()],
+        };
+
+        // Keep the original Date header as the received date and do not let the import land in spam
+        let res = self.client
+            .post("https://gmail.googleapis.com/gmail/v1/users/me/messages/import?internalDateSource=dateHeader&neverMarkSpam=true")
+            .bearer_auth(token)
+            .json(&json!({
+                "raw": URL_SAFE_NO_PAD.encode(&raw),
+                "labelIds": label_ids,
+            }))
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+            return Err(AppError::GmailApi(res.error_for_status().unwrap_err()));
+        }
+
+        let data: serde_json::Value = res.json().await?;
+        Ok(json!({
+            "id": data["id"],
+            "thread_id": data["threadId"],
+        }))
+    }
+
     async fn get_profile(&self, token: &str) -> Result<crate::handlers::provider::UserProfile, AppError> {
         let url = "https://gmail.googleapis.com/gmail/v1/users/me/profile";
         
@@ -526,13 +1083,467 @@ impl EmailProvider for GmailProvider {
     }
 }
 
-// Simple hash for cache keys
-fn simple_hash(s: &str) -> String {
-    use std::collections::hash_map::DefaultHasher;
-    use std::hash::{Hash, Hasher};
-    let mut hasher = DefaultHasher::new();
-    s.hash(&mut hasher);
-    hasher.finish().to_string()
+// Build the raw RFC 822 message (base64url encoded) used by messages.send and drafts.*
+// `sender` is only needed for a custom From name or a read receipt, Gmail fills in From otherwise
+fn build_raw_message(req: &SendMessageRequest, sender: Option<&EmailAddress>) -> String {
+    // Recipients were validated and normalized by SendMessageRequest::sanitize_headers
+    let mut builder = MimeBuilder::new(&req.body);
+    if let Some(sender) = sender {
+        builder = builder.address_header("From", &[sender.to_string()]);
+        if req.request_read_receipt {
+            builder = builder.address_header("Disposition-Notification-To", std::slice::from_ref(&sender.address));
+        }
+    }
+    builder = builder
+        .address_header("To", &req.to)
+        .address_header("Cc", req.cc.as_deref().unwrap_or_default())
+        // Gmail delivers to Bcc recipients and strips the header from the sent copy
+        .address_header("Bcc", req.bcc.as_deref().unwrap_or_default())
+        .address_header("Reply-To", req.reply_to.as_deref().unwrap_or_default())
+        .header("Subject", req.subject.clone())
+        .header("In-Reply-To", req.in_reply_to.clone().unwrap_or_default())
+        .header("References", req.references.clone().unwrap_or_default());
+    for (name, value) in req.importance.iter().flat_map(|i| i.mime_headers()) {
+        builder = builder.header(name, value);
+    }
+    for (name, value) in req.headers.iter().flatten() {
+        builder = builder.header(name, value.clone());
+    }
+    let raw = builder
+        .attachments(req.attachments.as_deref().unwrap_or_default())
+        .build();
+
+    URL_SAFE_NO_PAD.encode(raw)
+}
+
+// Convert a format=full message resource, with its decoded text/html bodies, into a CleanMessage
+fn parse_full_message(data: &serde_json::Value, body_text: Option<String>, body_html: Option<String>, attachments: Vec<AttachmentSummary>) -> CleanMessage {
+    let payload = &data["payload"];
+    let raw_from = payload_header(payload, "From");
+    let raw_date = payload_header(payload, "Date");
+
+    CleanMessage {
+        id: data["id"].as_str().unwrap_or("").to_string(),
+        thread_id: data["threadId"].as_str().map(|s| s.to_string()),
+        subject: payload_header(payload, "Subject"),
+        from: raw_from.as_deref().map(EmailAddress::parse),
+        to: payload_header(payload, "To").map(|v| split_address_list(&v).iter().map(|a| EmailAddress::parse(a)).collect()),
+        cc: payload_header(payload, "Cc").map(|v| split_address_list(&v).iter().map(|a| EmailAddress::parse(a)).collect()),
+        date: internal_date(data).or_else(|| raw_date.as_deref().and_then(normalize_date)),
+        snippet: data["snippet"].as_str().unwrap_or("").to_string(),
+        body_text,
+        body_html,
+        attachments,
+        reply_text: None,
+        raw_date,
+        raw_from,
+    }
+}
+
+// Summary of a whole thread (threads.get format=metadata): its latest message plus count and participants
+fn summarize_thread(thread_id: &str, data: &serde_json::Value) -> Result<MessageSummary, AppError> {
+    let mut messages: Vec<(i64, MessageSummary)> = data["messages"]
+        .as_array()
+        .map(|arr| arr.iter().map(|m| {
+            // internalDate (ms since epoch) is reliable, unlike the sender's Date header
+            let received = m["internalDate"].as_str().and_then(|d| d.parse().ok()).unwrap_or(0);
+            (received, parse_message_metadata(m))
+        }).collect())
+        .unwrap_or_default();
+    messages.sort_by_key(|(received, _)| *received);
+
+    let count = messages.len() as u32;
+    let unread = messages.iter().any(|(_, m)| m.unread);
+    let has_attachments = messages.iter().any(|(_, m)| m.has_attachments);
+    let flagged = messages.iter().any(|(_, m)| m.flagged);
+
+    let mut participants: Vec<EmailAddress> = Vec::new();
+    for from in messages.iter().filter_map(|(_, m)| m.from.as_ref()) {
+        if !participants.iter().any(|p| p.address.eq_ignore_ascii_case(&from.address)) {
+            participants.push(from.clone());
+        }
+    }
+
+    let (_, mut latest) = messages.pop()
+        .ok_or_else(|| anyhow::anyhow!("Thread {} has no messages", thread_id))?;
+    latest.unread = unread;
+    latest.has_attachments = has_attachments;
+    latest.flagged = flagged;
+    latest.messages_in_thread = Some(count);
+    latest.participants = Some(participants);
+
+    Ok(latest)
+}
+
+// Splits a Gmail batch response (multipart/mixed of application/http parts) into the JSON
+// bodies of the successful parts, indexed by the "item-N" Content-ID we sent
+fn parse_batch_response(body: &str, boundary: &str, count: usize) -> Vec<Option<serde_json::Value>> {
+    let mut results = vec![None; count];
+    let body = body.replace("\r\n", "\n");
+
+    for part in body.split(&format!("--{}", boundary)) {
+        // Part headers, then the embedded HTTP status line + headers, then the JSON body
+        let mut sections = part.trim_start_matches('\n').splitn(3, "\n\n");
+        let (Some(part_headers), Some(http_head), Some(json)) = (sections.next(), sections.next(), sections.next()) else {
+            continue;
+        };
+
+        let index = part_headers.lines()
+            .find(|l| l.to_lowercase().starts_with("content-id:"))
+            .and_then(|l| l.trim().trim_end_matches('>').rsplit('-').next())
+            .and_then(|n| n.parse::<usize>().ok());
+        let ok = http_head.lines().next()
+            .and_then(|status_line| status_line.split_whitespace().nth(1))
+            == Some("200");
+
+        if let (Some(index), true) = (index, ok) {
+            if let Some(slot) = results.get_mut(index) {
+                *slot = serde_json::from_str(json.trim()).ok();
+            }
+        }
+    }
+
+    results
+}
+
+// Convert a message resource fetched with format=metadata into a MessageSummary
+fn parse_message_metadata(data: &serde_json::Value) -> MessageSummary {
+    // Parse headers
+    let header = |name: &str| payload_header(&data["payload"], name);
+
+    let subject = header("Subject");
+    let raw_from = header("From");
+    let raw_date = header("Date");
+    let from = raw_from.as_deref().map(EmailAddress::parse);
+    let date = internal_date(data).or_else(|| raw_date.as_deref().and_then(normalize_date));
+    let to = header("To").map(|v| split_address_list(&v).iter().map(|a| EmailAddress::parse(a)).collect());
+    let cc = header("Cc").map(|v| split_address_list(&v).iter().map(|a| EmailAddress::parse(a)).collect());
+
+    let label_ids: Vec<String> = data["labelIds"]
+        .as_array()
+        .map(|labels| labels.iter().filter_map(|l| l.as_str().map(|s| s.to_string())).collect())
+        .unwrap_or_default();
+
+    // Check if unread (labelIds contains "UNREAD")
+    let unread = label_ids.iter().any(|l| l == "UNREAD");
+    let flagged = label_ids.iter().any(|l| l == "STARRED");
+
+    // Inbox tabs show up as CATEGORY_SOCIAL, CATEGORY_PROMOTIONS, ...
+    let categories = label_ids.iter()
+        .filter_map(|l| l.strip_prefix("CATEGORY_"))
+        .map(|c| c.to_lowercase())
+        .collect();
+
+    let importance = header("Importance")
+        .map(|i| i.trim().to_lowercase())
+        .or_else(|| header("X-Priority").map(|p| x_priority_importance(&p).to_string()))
+        .unwrap_or_else(|| "normal".to_string());
+
+    // Check for attachments
+    let has_attachments = has_attachments_in_payload(&data["payload"]);
+    
+    let snippet = data["snippet"].as_str().unwrap_or("").to_string();
+    
+    MessageSummary {
+        id: data["id"].as_str().unwrap_or("").to_string(),
+        thread_id: data["threadId"].as_str().unwrap_or("").to_string(),
+        snippet,
+        subject,
+        from,
+        date,
+        unread,
+        has_attachments,
+        messages_in_thread: None, // Not set for individual message fetch
+        participants: None,
+        to,
+        cc,
+        label_ids,
+        size_estimate: data["sizeEstimate"].as_u64(),
+        importance: Some(importance),
+        flagged,
+        categories,
+        raw_date,
+        raw_from,
+    }
+}
+
+// metadataHeaders needed by parse_message_metadata
+const SUMMARY_HEADERS: &str = "metadataHeaders=Subject&metadataHeaders=From&metadataHeaders=Date&metadataHeaders=To&metadataHeaders=Cc&metadataHeaders=Importance&metadataHeaders=X-Priority";
+
+// X-Priority: 1 (Highest) .. 5 (Lowest)
+fn x_priority_importance(value: &str) -> &'static str {
+    match value.trim().chars().next() {
+        Some('1') | Some('2') => "high",
+        Some('4') | Some('5') => "low",
+        _ => "normal",
+    }
+}
+
+// internalDate (milliseconds since the epoch, as a string) as RFC 3339 UTC
+fn internal_date(data: &serde_json::Value) -> Option<String> {
+    data["internalDate"].as_str().and_then(|d| d.parse().ok()).and_then(date_from_millis)
+}
+
+// messages.list (or threads.list) URL for the given filters and Gmail page token
+fn list_url(params: &ListParams, page_token: Option<&str>) -> Result<String, AppError> {
+    // Build query params
+    let mut query = Vec::new();
+    query.push(format!("maxResults={}", params.max_results.unwrap_or(10)));
+
+    let mut q_terms = Vec::new();
+    if let Some(q) = &params.q {
+        q_terms.push(SearchQuery::parse(q)?.to_gmail());
+    }
+    if let Some(labels) = &params.label_ids {
+        for label in labels.split(',').map(str::trim) {
+            if label == "ARCHIVE" {
+                q_terms.push(GMAIL_ARCHIVE_QUERY.to_string());
+            } else {
+                query.push(format!("labelIds={}", label));
+            }
+        }
+    }
+    if !q_terms.is_empty() {
+        query.push(format!("q={}", urlencoding::encode(&q_terms.join(" "))));
+    }
+
+    // Use the resolved token
+    if let Some(t) = page_token {
+        query.push(format!("pageToken={}", urlencoding::encode(t)));
+    }
+
+    let resource = if params.collapse_threads.unwrap_or(false) { "threads" } else { "messages" };
+    Ok(format!("https://gmail.googleapis.com/gmail/v1/users/me/{}?{}", resource, query.join("&")))
+}
+
+// ARCHIVE is virtual on Gmail: archiving drops INBOX and un-archiving restores it
+fn gmail_label_changes(add: Vec<String>, remove: Vec<String>) -> (Vec<String>, Vec<String>) {
+    let mut add_ids = Vec::new();
+    let mut remove_ids = Vec::new();
+
+    for id in add {
+        if id == "ARCHIVE" { remove_ids.push("INBOX".to_string()) } else { add_ids.push(id) }
+    }
+    for id in remove {
+        if id == "ARCHIVE" { add_ids.push("INBOX".to_string()) } else { remove_ids.push(id) }
+    }
+
+    (add_ids, remove_ids)
+}
+
+// Message resource for drafts.create / drafts.update
+fn draft_message_body(req: &SendMessageRequest, sender: Option<&EmailAddress>) -> serde_json::Value {
+    let mut message = json!({ "raw": build_raw_message(req, sender) });
+    if let Some(thread_id) = req.thread_id.as_deref().filter(|t| !t.is_empty()) {
+        message["threadId"] = json!(thread_id);
+    }
+    message
+}
+
+// Convert a Gmail draft resource (any format) into a Draft
+fn parse_draft(data: &serde_json::Value) -> Draft {
+    let message = &data["message"];
+    let header = |name: &str| payload_header(&message["payload"], name);
+
+    Draft {
+        id: data["id"].as_str().unwrap_or("").to_string(),
+        message_id: message["id"].as_str().map(|s| s.to_string()),
+        thread_id: message["threadId"].as_str().map(|s| s.to_string()),
+        subject: header("Subject"),
+        to: header("To").map(|t| split_address_list(&t)),
+        snippet: message["snippet"].as_str().map(|s| s.to_string()),
+        date: header("Date").as_deref().and_then(normalize_date),
+    }
+}
+
+// Gmail's cap on the size of a sent message
+const MAX_FORWARD_SIZE: usize = 25 * 1024 * 1024;
+
+// To and Cc of a reply: the request's own lists win, otherwise the original Reply-To (or From)
+// and, for reply-all, everyone on its To/Cc. The own address is left out; answering one of our
+// own sent messages goes to its recipients, as in Gmail. Everything goes through the address
+// module, so a malformed original header is reported as InvalidAddresses.
+fn reply_recipients(mode: ReplyMode, header: &dyn Fn(&str) -> Option<String>, me: &str, req: &ReplyRequest) -> Result<(Vec<String>, Vec<String>), AppError> {
+    let original = |name: &str| header(name).map(|h| split_address_list(&h)).unwrap_or_default();
+    let not_me = |addresses: Vec<String>| -> Vec<String> {
+        addresses.into_iter().filter(|a| !EmailAddress::parse(a).address.eq_ignore_ascii_case(me)).collect()
+    };
+
+    let (to, cc) = match mode {
+        ReplyMode::Forward => {
+            let to = req.to.clone().unwrap_or_default();
+            if to.is_empty() {
+                return Err(AppError::BadRequest("Forward requires at least one recipient in 'to'".to_string()));
+            }
+            (to, req.cc.clone().unwrap_or_default())
+        }
+        ReplyMode::Reply | ReplyMode::ReplyAll => {
+            let to = match &req.to {
+                Some(to) => to.clone(),
+                None => {
+                    let reply_to = original("Reply-To");
+                    let target = not_me(if reply_to.is_empty() { original("From") } else { reply_to });
+                    if target.is_empty() { not_me(original("To")) } else { target }
+                }
+            };
+            let mut cc = Vec::new();
+            if mode == ReplyMode::ReplyAll {
+                cc.extend(not_me(original("To")));
+                cc.extend(not_me(original("Cc")));
+            }
+            cc.extend(req.cc.clone().unwrap_or_default());
+            (to, cc)
+        }
+    };
+
+    // Deduplicates as well: anyone already in To is dropped from Cc
+    let recipients = normalize_recipients(&to, &cc, &[], &[])?;
+    Ok((format_addresses(&recipients.to), format_addresses(&recipients.cc)))
+}
+
+// "Re:"/"Fwd:" prefix, without stacking it on a subject that already has one
+fn prefixed_subject(subject: &str, prefix: &str) -> String {
+    if subject.to_lowercase().starts_with(&prefix.to_lowercase()) {
+        subject.to_string()
+    } else {
+        format!("{} {}", prefix, subject)
+    }
+}
+
+// Convert a Gmail label resource into a Label
+fn parse_label(l: &serde_json::Value) -> Label {
+    Label {
+        id: l["id"].as_str().unwrap_or("").to_string(),
+        name: l["name"].as_str().unwrap_or("").to_string(),
+        label_type: l["type"].as_str().map(|s| s.to_string()),
+        color: l["color"]["backgroundColor"].as_str().map(|bg| LabelColor {
+            text_color: l["color"]["textColor"].as_str().unwrap_or_default().to_string(),
+            background_color: bg.to_string(),
+        }),
+        unread_count: l["messagesUnread"].as_u64(),
+        total_count: l["messagesTotal"].as_u64(),
+        ..Default::default()
+    }
+}
+
+// "Customers/Acme" renamed to "Globex" stays under "Customers"
+fn sibling_label_name(current: &str, name: &str) -> String {
+    match current.rsplit_once('/') {
+        Some((parent, _)) => format!("{}/{}", parent, name),
+        None => name.to_string(),
+    }
+}
+
+// Id and new name of every label nested under `old_name`, for a parent renamed to `new_name`
+fn child_label_renames(labels: &[serde_json::Value], old_name: &str, new_name: &str) -> Vec<(String, String)> {
+    let prefix = format!("{}/", old_name);
+    labels.iter()
+        .filter_map(|l| {
+            let rest = l["name"].as_str()?.strip_prefix(&prefix)?;
+            Some((l["id"].as_str()?.to_string(), format!("{}/{}", new_name, rest)))
+        })
+        .collect()
+}
+
+fn label_color_json(color: &LabelColor) -> serde_json::Value {
+    json!({
+        "textColor": color.text_color,
+        "backgroundColor": color.background_color,
+    })
+}
+
+// Gmail identifies MIME parts by their position in the tree ("0", "1.0", ...). Unlike the
+// attachmentId it hands out, the partId is stable, so we expose it as our attachment id.
+fn gmail_part_ids(message: &Message) -> HashMap<usize, String> {
+    let mut ids = HashMap::new();
+    let mut stack = vec![(0usize, String::new())];
+
+    while let Some((idx, path)) = stack.pop() {
+        if let Some(PartType::Multipart(children)) = message.parts.get(idx).map(|p| &p.body) {
+            for (i, child) in children.iter().enumerate() {
+                let child_path = if path.is_empty() { i.to_string() } else { format!("{}.{}", path, i) };
+                stack.push((*child, child_path));
+            }
+        }
+        ids.insert(idx, path);
+    }
+
+    ids
+}
+
+#[derive(Default)]
+struct PayloadWalk<'a> {
+    text: Option<&'a serde_json::Value>,
+    html: Option<&'a serde_json::Value>,
+    attachments: Vec<AttachmentSummary>,
+}
+
+// Picks the first text/plain and text/html bodies and lists every other leaf as an attachment
+fn walk_payload<'a>(part: &'a serde_json::Value, walk: &mut PayloadWalk<'a>) {
+    if let Some(children) = part["parts"].as_array() {
+        for child in children {
+            walk_payload(child, walk);
+        }
+        return;
+    }
+
+    let mime_type = part["mimeType"].as_str().unwrap_or("application/octet-stream").to_lowercase();
+    let filename = part["filename"].as_str().filter(|f| !f.is_empty());
+    let is_attachment = filename.is_some()
+        || payload_header(part, "Content-Disposition").is_some_and(|d| d.trim().to_lowercase().starts_with("attachment"));
+
+    if !is_attachment && mime_type == "text/plain" && walk.text.is_none() {
+        walk.text = Some(part);
+    } else if !is_attachment && mime_type == "text/html" && walk.html.is_none() {
+        walk.html = Some(part);
+    } else if is_attachment || !mime_type.starts_with("text/") {
+        walk.attachments.push(AttachmentSummary {
+            filename: filename.unwrap_or("unnamed").to_string(),
+            content_type: mime_type,
+            size: part["body"]["size"].as_u64().unwrap_or(0) as usize,
+            id: part["partId"].as_str().map(|s| s.to_string()),
+            content_id: payload_header(part, "Content-ID").map(|c| c.trim().trim_start_matches('<').trim_end_matches('>').to_string()),
+        });
+    }
+}
+
+// Case-insensitive header lookup on a payload part
+fn payload_header(part: &serde_json::Value, name: &str) -> Option<String> {
+    part["headers"]
+        .as_array()?
+        .iter()
+        .find(|h| h["name"].as_str().is_some_and(|n| n.eq_ignore_ascii_case(name)))
+        .and_then(|h| h["value"].as_str())
+        .map(|s| s.to_string())
+}
+
+// `charset` parameter of a part's Content-Type, unquoted
+fn part_charset(part: &serde_json::Value) -> Option<String> {
+    let content_type = payload_header(part, "Content-Type")?;
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name.trim().eq_ignore_ascii_case("charset").then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+// Text in the given charset (UTF-8 when missing or unknown) as a String
+fn decode_text(bytes: &[u8], charset: Option<&str>) -> String {
+    let encoding = charset
+        .and_then(|c| encoding_rs::Encoding::for_label(c.as_bytes()))
+        .unwrap_or(encoding_rs::UTF_8);
+    encoding.decode(bytes).0.into_owned()
+}
+
+// Recursively find a payload part by its partId
+fn find_part<'a>(payload: &'a serde_json::Value, part_id: &str) -> Option<&'a serde_json::Value> {
+    if payload["partId"].as_str() == Some(part_id) {
+        return Some(payload);
+    }
+
+    payload["parts"]
+        .as_array()?
+        .iter()
+        .find_map(|part| find_part(part, part_id))
 }
 
 // Recursively check if payload has attachments
@@ -553,3 +1564,231 @@ fn has_attachments_in_payload(payload: &serde_json::Value) -> bool {
     
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Shape of a real https://gmail.googleapis.com/batch/gmail/v1 response (CRLF line endings)
+    fn batch_response(parts: &[(&str, &str, &str)]) -> String {
+        let mut body = String::new();
+        for (content_id, status, json) in parts {
+            body.push_str(&format!(
+                "--batch_Zx9\r\nContent-Type: application/http\r\nContent-ID: <response-{}>\r\n\r\n\
+                 HTTP/1.1 {}\r\nContent-Type: application/json; charset=UTF-8\r\nVary: Origin\r\nVary: X-Origin\r\nVary: Referer\r\n\r\n\
+                 {}\r\n",
+                content_id, status, json
+            ));
+        }
+        body.push_str("--batch_Zx9--\r\n");
+        body
+    }
+
+    #[test]
+    fn batch_response_is_indexed_by_content_id() {
+        let body = batch_response(&[
+            ("item-2", "200 OK", "{\n  \"id\": \"c\",\n  \"threadId\": \"t3\"\n}\n"),
+            ("item-0", "200 OK", "{\n  \"id\": \"a\",\n  \"threadId\": \"t1\"\n}\n"),
+            ("item-1", "200 OK", "{\n  \"id\": \"b\",\n  \"threadId\": \"t2\"\n}\n"),
+        ]);
+
+        let results = parse_batch_response(&body, "batch_Zx9", 3);
+        let ids: Vec<Option<&str>> = results.iter().map(|r| r.as_ref().and_then(|v| v["id"].as_str())).collect();
+        assert_eq!(ids, vec![Some("a"), Some("b"), Some("c")]);
+    }
+
+    #[test]
+    fn failed_and_missing_batch_parts_are_none() {
+        let body = batch_response(&[
+            ("item-0", "200 OK", "{\"id\": \"a\"}"),
+            ("item-1", "429 Too Many Requests", "{\n  \"error\": {\n    \"code\": 429,\n    \"message\": \"Too many concurrent requests for user\"\n  }\n}\n"),
+            ("item-3", "404 Not Found", "{\"error\": {\"code\": 404, \"message\": \"Requested entity was not found.\"}}"),
+            // Out of range ids are ignored
+            ("item-9", "200 OK", "{\"id\": \"z\"}"),
+        ]);
+
+        let results = parse_batch_response(&body, "batch_Zx9", 4);
+        assert_eq!(results[0].as_ref().and_then(|v| v["id"].as_str()), Some("a"));
+        assert!(results[1].is_none());
+        assert!(results[2].is_none());
+        assert!(results[3].is_none());
+    }
+
+    #[test]
+    fn text_parts_are_decoded_from_their_charset() {
+        let part = json!({ "headers": [{ "name": "content-type", "value": "text/plain; format=flowed; charset=\"ISO-8859-1\"" }] });
+        assert_eq!(part_charset(&part).as_deref(), Some("ISO-8859-1"));
+        assert_eq!(decode_text(b"Gr\xfc\xdfe", part_charset(&part).as_deref()), "Grüße");
+
+        assert_eq!(decode_text(b"\x82\xb1\x82\xf1\x82\xc9\x82\xbf\x82\xcd", Some("Shift_JIS")), "こんにちは");
+        assert_eq!(decode_text(b"\xcf\xf0\xe8\xe2\xe5\xf2", Some("windows-1251")), "Привет");
+        assert_eq!(decode_text("Grüße".as_bytes(), None), "Grüße");
+        assert_eq!(decode_text("Grüße".as_bytes(), Some("x-unknown")), "Grüße");
+    }
+
+    // Fields that carry a value, attachment fields included, ignoring the raw_* debugging fields
+    fn shape(message: &CleanMessage) -> Vec<String> {
+        let value = serde_json::to_value(message).unwrap();
+        let mut fields: Vec<String> = value.as_object().unwrap().iter()
+            .filter(|(name, v)| !v.is_null() && !name.starts_with("raw_"))
+            .map(|(name, _)| name.clone())
+            .collect();
+        for attachment in value["attachments"].as_array().unwrap() {
+            fields.extend(attachment.as_object().unwrap().iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(name, _)| format!("attachments.{}", name)));
+        }
+        fields.sort();
+        fields.dedup();
+        fields
+    }
+
+    #[test]
+    fn thread_messages_have_the_same_shape_as_outlook() {
+        let gmail = json!({
+            "id": "m1",
+            "threadId": "t1",
+            "snippet": "Hello",
+            "internalDate": "1700000000000",
+            "payload": {
+                "mimeType": "multipart/mixed",
+                "headers": [
+                    { "name": "From", "value": "Anna <anna@example.com>" },
+                    { "name": "To", "value": "\"Doe, John\" <john@example.com>" },
+                    { "name": "Cc", "value": "sales@example.com" },
+                    { "name": "Subject", "value": "Hello" },
+                    { "name": "Date", "value": "Tue, 14 Nov 2023 22:13:20 +0000" },
+                ],
+                "parts": [
+                    { "partId": "0", "mimeType": "multipart/alternative", "parts": [
+                        { "partId": "0.0", "mimeType": "text/plain", "body": { "size": 5, "data": "SGVsbG8" } },
+                        { "partId": "0.1", "mimeType": "text/html", "body": { "size": 12, "data": "PHA-SGVsbG88L3A-" } },
+                    ] },
+                    { "partId": "1", "mimeType": "image/png", "filename": "logo.png",
+                      "headers": [{ "name": "Content-ID", "value": "<logo@example.com>" }],
+                      "body": { "size": 42, "attachmentId": "ANGjdJ8" } },
+                ],
+            },
+        });
+        let mut walk = PayloadWalk::default();
+        walk_payload(&gmail["payload"], &mut walk);
+        let gmail = parse_full_message(&gmail, Some("Hello".to_string()), Some("<p>Hello</p>".to_string()), walk.attachments);
+
+        let outlook = json!({
+            "id": "AAMk1",
+            "conversationId": "AAQk1",
+            "subject": "Hello",
+            "bodyPreview": "Hello",
+            "receivedDateTime": "2023-11-14T22:13:20Z",
+            "from": { "emailAddress": { "name": "Anna", "address": "anna@example.com" } },
+            "toRecipients": [{ "emailAddress": { "name": "Doe, John", "address": "john@example.com" } }],
+            "ccRecipients": [{ "emailAddress": { "name": "sales@example.com", "address": "sales@example.com" } }],
+            "body": { "contentType": "html", "content": "<p>Hello</p>" },
+        });
+        let text = json!({ "body": { "contentType": "text", "content": "Hello" } });
+        let attachment = json!({ "id": "AAMkAtt", "name": "logo.png", "contentType": "image/png", "size": 42, "isInline": true });
+        let outlook = super::super::outlook::parse_full_message(
+            &outlook,
+            &text,
+            vec![super::super::outlook::parse_attachment(&attachment, Some("logo@example.com".to_string()))],
+        );
+
+        assert_eq!(shape(&gmail), shape(&outlook));
+        assert!(shape(&gmail).contains(&"body_html".to_string()));
+        assert!(shape(&gmail).contains(&"attachments.content_id".to_string()));
+        assert_eq!(gmail.to, outlook.to);
+        assert_eq!(gmail.date, outlook.date);
+    }
+
+    #[test]
+    fn draft_headers_are_parsed_like_messages() {
+        let draft = parse_draft(&json!({
+            "id": "r-1",
+            "message": {
+                "id": "m1",
+                "threadId": "t1",
+                "payload": { "headers": [
+                    { "name": "subject", "value": "Offer" },
+                    { "name": "TO", "value": "\"Doe, John\" <john@example.com>, anna@example.com" },
+                    { "name": "date", "value": "Tue, 14 Nov 2023 23:13:20 +0100" },
+                ] },
+            },
+        }));
+
+        assert_eq!(draft.subject.as_deref(), Some("Offer"));
+        assert_eq!(draft.to.unwrap(), vec!["\"Doe, John\" <john@example.com>", "anna@example.com"]);
+        assert_eq!(draft.date.as_deref(), Some("2023-11-14T22:13:20Z"));
+    }
+
+    fn reply_headers(name: &str) -> Option<String> {
+        match name {
+            "From" => Some("Anna <anna@example.com>".to_string()),
+            "To" => Some("\"Me, Myself\" <ME@example.com>, \"Doe, John\" <john@example.com>".to_string()),
+            "Cc" => Some("undisclosed-recipients:;, ANNA@example.com, carol@example.com".to_string()),
+            _ => None,
+        }
+    }
+
+    fn reply_request(to: Option<&[&str]>) -> ReplyRequest {
+        ReplyRequest {
+            body: "<p>Thanks</p>".to_string(),
+            to: to.map(|to| to.iter().map(|a| a.to_string()).collect()),
+            cc: None,
+            attachments: None,
+        }
+    }
+
+    #[test]
+    fn reply_all_leaves_out_the_sender_and_me() {
+        let (to, cc) = reply_recipients(ReplyMode::ReplyAll, &reply_headers, "me@example.com", &reply_request(None)).unwrap();
+        assert_eq!(to, vec!["\"Anna\" <anna@example.com>"]);
+        assert_eq!(cc, vec!["\"Doe, John\" <john@example.com>", "carol@example.com"]);
+
+        let (to, cc) = reply_recipients(ReplyMode::Reply, &reply_headers, "me@example.com", &reply_request(None)).unwrap();
+        assert_eq!(to, vec!["\"Anna\" <anna@example.com>"]);
+        assert!(cc.is_empty());
+    }
+
+    #[test]
+    fn reply_to_own_message_goes_to_its_recipients() {
+        let (to, cc) = reply_recipients(ReplyMode::ReplyAll, &reply_headers, "anna@example.com", &reply_request(None)).unwrap();
+        assert_eq!(to, vec!["\"Me, Myself\" <ME@example.com>", "\"Doe, John\" <john@example.com>"]);
+        assert_eq!(cc, vec!["carol@example.com"]);
+    }
+
+    #[test]
+    fn reply_rejects_malformed_original_recipients() {
+        let headers = |name: &str| (name == "Reply-To").then(|| "not-an-address".to_string());
+        let result = reply_recipients(ReplyMode::Reply, &headers, "me@example.com", &reply_request(None));
+        assert!(matches!(result, Err(AppError::InvalidAddresses(_))));
+
+        let result = reply_recipients(ReplyMode::Forward, &headers, "", &reply_request(Some(&[])));
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+    }
+
+    #[test]
+    fn renamed_labels_keep_their_parent_and_children() {
+        assert_eq!(sibling_label_name("Customers/Acme", "Globex"), "Customers/Globex");
+        assert_eq!(sibling_label_name("Customers/Europe/Acme", "Globex"), "Customers/Europe/Globex");
+        assert_eq!(sibling_label_name("Customers", "Clients"), "Clients");
+
+        let labels = vec![
+            json!({ "id": "Label_1", "name": "Customers" }),
+            json!({ "id": "Label_2", "name": "Customers/Acme" }),
+            json!({ "id": "Label_3", "name": "Customers/Europe/Globex" }),
+            json!({ "id": "Label_4", "name": "CustomersArchive" }),
+            json!({ "id": "INBOX", "name": "INBOX" }),
+        ];
+        assert_eq!(child_label_renames(&labels, "Customers", "Clients"), vec![
+            ("Label_2".to_string(), "Clients/Acme".to_string()),
+            ("Label_3".to_string(), "Clients/Europe/Globex".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn batch_response_with_bare_newlines_is_parsed() {
+        let body = batch_response(&[("item-0", "200 OK", "{\"id\": \"a\"}")]).replace("\r\n", "\n");
+        let results = parse_batch_response(&body, "batch_Zx9", 1);
+        assert_eq!(results[0].as_ref().and_then(|v| v["id"].as_str()), Some("a"));
+    }
+}
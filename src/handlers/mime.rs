@@ -0,0 +1,388 @@
+use std::sync::OnceLock;
+
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use regex::Regex;
+
+use crate::error::AppError;
+use super::provider::{Attachment, EmailAddress};
+
+// Raw RFC 5322 message composition for the providers that send MIME rather than JSON (Gmail).
+// Layout: multipart/mixed -> [multipart/related -> [multipart/alternative -> text/plain, text/html]
+// + inline images] + attachments, where the text/plain alternative is generated from the HTML body.
+// Levels without parts of their own (no inline images, no attachments) are left out.
+
+/// RFC 2045 limit for base64 lines
+const BASE64_LINE_LENGTH: usize = 76;
+
+/// Input bytes per RFC 2047 encoded-word: "=?UTF-8?B?" + 60 base64 chars + "?=" stays under 75
+const ENCODED_WORD_BYTES: usize = 45;
+
+/// Encoded characters per RFC 2231 parameter continuation
+const PARAMETER_SEGMENT_LENGTH: usize = 60;
+
+pub struct MimeBuilder<'a> {
+    headers: Vec<(String, String)>,
+    html: &'a str,
+    attachments: &'a [Attachment],
+}
+
+impl<'a> MimeBuilder<'a> {
+    pub fn new(html: &'a str) -> Self {
+        Self {
+            headers: Vec::new(),
+            html,
+            attachments: &[],
+        }
+    }
+
+    /// Adds an unstructured top-level header (RFC 2047 encoded if needed), empty values are left out
+    pub fn header(mut self, name: &str, value: impl Into<String>) -> Self {
+        let value = value.into();
+        if !value.trim().is_empty() {
+            self.headers.push((name.to_string(), encode_header(&value)));
+        }
+        self
+    }
+
+    /// Adds an address list header; only display names are encoded, addresses stay as-is
+    pub fn address_header(mut self, name: &str, addresses: &[String]) -> Self {
+        let value = addresses.iter()
+            .map(|a| format_address(&EmailAddress::parse(a)))
+            .collect::<Vec<_>>()
+            .join(",\r\n ");
+        if !value.is_empty() {
+            self.headers.push((name.to_string(), value));
+        }
+        self
+    }
+
+    pub fn attachments(mut self, attachments: &'a [Attachment]) -> Self {
+        self.attachments = attachments;
+        self
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let mut out = String::new();
+        for (name, value) in &self.headers {
+            out.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        out.push_str("MIME-Version: 1.0\r\n");
+
+        let (inline, attached): (Vec<&Attachment>, Vec<&Attachment>) =
+            self.attachments.iter().partition(|a| a.content_id.is_some());
+
+        let text = html_to_text(self.html);
+        let mut body = multipart("alternative", "", |parts, boundary| {
+            push_part(parts, boundary, "text/plain; charset=utf-8", &[], text.as_bytes());
+            push_part(parts, boundary, "text/html; charset=utf-8", &[], self.html.as_bytes());
+        });
+
+        // Images the HTML references as cid: travel next to it in multipart/related
+        if !inline.is_empty() {
+            body = multipart("related", "; type=\"multipart/alternative\"", |parts, boundary| {
+                push_nested(parts, boundary, &body);
+                for att in &inline {
+                    push_attachment(parts, boundary, att, "inline");
+                }
+            });
+        }
+
+        if !attached.is_empty() {
+            body = multipart("mixed", "", |parts, boundary| {
+                push_nested(parts, boundary, &body);
+                for att in &attached {
+                    push_attachment(parts, boundary, att, "attachment");
+                }
+            });
+        }
+
+        out.push_str(&body);
+        out.into_bytes()
+    }
+}
+
+// A multipart entity: its Content-Type header, a blank line, the parts and the closing boundary
+fn multipart(subtype: &str, params: &str, fill: impl FnOnce(&mut String, &str)) -> String {
+    let boundary = boundary(subtype);
+    let mut out = format!("Content-Type: multipart/{}{}; boundary=\"{}\"\r\n\r\n", subtype, params, boundary);
+    fill(&mut out, &boundary);
+    out.push_str(&format!("--{}--\r\n", boundary));
+    out
+}
+
+// A complete entity built by multipart() as one part of an enclosing multipart
+fn push_nested(out: &mut String, boundary: &str, entity: &str) {
+    out.push_str(&format!("--{}\r\n", boundary));
+    out.push_str(entity);
+    out.push_str("\r\n");
+}
+
+fn push_attachment(out: &mut String, boundary: &str, att: &Attachment, disposition: &str) {
+    let (name, filename) = filename_params(&att.filename);
+    let content_type = format!("{}; {}", att.mime_type, name);
+    let mut headers = vec![format!("Content-Disposition: {}; {}", disposition, filename)];
+    if let Some(content_id) = &att.content_id {
+        headers.push(format!("Content-ID: <{}>", content_id));
+    }
+    push_part(out, boundary, &content_type, &headers, &att.content);
+}
+
+// One base64 leaf part, preceded by its boundary line
+fn push_part(out: &mut String, boundary: &str, content_type: &str, headers: &[String], body: &[u8]) {
+    out.push_str(&format!("--{}\r\n", boundary));
+    out.push_str(&format!("Content-Type: {}\r\n", content_type));
+    for header in headers {
+        out.push_str(header);
+        out.push_str("\r\n");
+    }
+    out.push_str("Content-Transfer-Encoding: base64\r\n\r\n");
+    out.push_str(&wrap_base64(body));
+    out.push_str("\r\n");
+}
+
+// Base64 split into CRLF-separated lines of at most 76 characters
+fn wrap_base64(body: &[u8]) -> String {
+    let encoded = STANDARD.encode(body);
+    // Base64 output is ASCII, so byte chunks are always valid UTF-8
+    encoded.as_bytes()
+        .chunks(BASE64_LINE_LENGTH)
+        .map(|line| std::str::from_utf8(line).unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("\r\n")
+}
+
+/// Free text headed for a header line (subject, filename). Line breaks are folded into a single
+/// space so they cannot start a new header; any other control character is rejected.
+pub fn sanitize_header_text(field: &str, value: &str) -> Result<String, AppError> {
+    if value.chars().any(|c| c.is_control() && !matches!(c, '\r' | '\n' | '\t')) {
+        return Err(AppError::BadRequest(format!("{} contains a control character", field)));
+    }
+    Ok(value.split(['\r', '\n']).map(str::trim).filter(|l| !l.is_empty()).collect::<Vec<_>>().join(" "))
+}
+
+/// Structured header values (addresses, Message-IDs, MIME types) never legitimately contain
+/// line breaks or control characters, so those are rejected outright.
+pub fn check_header_value(field: &str, value: &str) -> Result<(), AppError> {
+    if value.chars().any(char::is_control) {
+        return Err(AppError::BadRequest(format!("{} {:?} contains a line break or control character", field, value)));
+    }
+    Ok(())
+}
+
+/// Unstructured header value as RFC 2047 encoded-words ("=?UTF-8?B?...?="), unchanged if plain ASCII.
+/// Long values are split on character boundaries into several folded words.
+pub fn encode_header(value: &str) -> String {
+    if value.chars().all(|c| c.is_ascii() && !c.is_ascii_control()) {
+        return value.to_string();
+    }
+
+    let mut words = Vec::new();
+    let mut chunk = String::new();
+    for c in value.chars() {
+        if chunk.len() + c.len_utf8() > ENCODED_WORD_BYTES {
+            words.push(encoded_word(&chunk));
+            chunk.clear();
+        }
+        chunk.push(c);
+    }
+    if !chunk.is_empty() {
+        words.push(encoded_word(&chunk));
+    }
+
+    words.join("\r\n ")
+}
+
+fn encoded_word(chunk: &str) -> String {
+    format!("=?UTF-8?B?{}?=", STANDARD.encode(chunk.as_bytes()))
+}
+
+// "Name" <address>, with the display name quoted when ASCII and encoded otherwise
+fn format_address(address: &EmailAddress) -> String {
+    match address.name.as_deref() {
+        None => address.address.clone(),
+        Some(name) if name.chars().all(|c| c.is_ascii() && !c.is_ascii_control()) => {
+            format!("\"{}\" <{}>", quote_string(name), address.address)
+        }
+        Some(name) => format!("{} <{}>", encode_header(name), address.address),
+    }
+}
+
+fn quote_string(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// Content-Type `name` and Content-Disposition `filename` parameters. Non-ASCII names get an
+// RFC 2231 `filename*` (split into continuations when long) plus an encoded-word `name`,
+// which is what Outlook and older clients read instead.
+fn filename_params(filename: &str) -> (String, String) {
+    if filename.chars().all(|c| c.is_ascii() && !c.is_ascii_control()) {
+        let quoted = quote_string(filename);
+        return (format!("name=\"{}\"", quoted), format!("filename=\"{}\"", quoted));
+    }
+
+    let name = format!("name=\"{}\"", encode_header(filename).replace("\r\n ", " "));
+
+    let encoded = format!("UTF-8''{}", urlencoding::encode(filename));
+    if encoded.len() <= PARAMETER_SEGMENT_LENGTH {
+        return (name, format!("filename*={}", encoded));
+    }
+
+    // Segments end on character boundaries: some parsers decode each continuation on its own
+    let mut segments = vec![String::from("UTF-8''")];
+    for c in filename.chars() {
+        let encoded_char = urlencoding::encode(c.encode_utf8(&mut [0; 4])).into_owned();
+        if segments.last().is_some_and(|s| s.len() + encoded_char.len() > PARAMETER_SEGMENT_LENGTH) {
+            segments.push(String::new());
+        }
+        if let Some(segment) = segments.last_mut() {
+            segment.push_str(&encoded_char);
+        }
+    }
+    let filename = segments.iter().enumerate()
+        .map(|(i, segment)| format!("filename*{}*={}", i, segment))
+        .collect::<Vec<_>>()
+        .join(";\r\n ");
+
+    (name, filename)
+}
+
+// Unique per part, so attachment content can never contain it
+fn boundary(kind: &str) -> String {
+    format!("{}_{}", kind, uuid::Uuid::new_v4().simple())
+}
+
+/// Substitutions applied in order by `html_to_text`; the last one drops the remaining tags
+const HTML_TO_TEXT_RULES: [(&str, &str); 7] = [
+    // Invisible content
+    (r"(?is)<(head|style|script|title)\b[^>]*>.*?</(head|style|script|title)>", ""),
+    (r"(?s)<!--.*?-->", ""),
+    // Source line breaks carry no meaning in HTML
+    (r"[\r\n\t]+", " "),
+    (r#"(?is)<a\b[^>]*\bhref\s*=\s*["']?(https?://[^"'\s>]+)["']?[^>]*>(.*?)</a>"#, "$2 ($1)"),
+    (r"(?i)<li\b[^>]*>", "\n- "),
+    (r"(?i)<br\s*/?>|</?(p|div|tr|h[1-6]|ul|ol|table|blockquote)\b[^>]*>", "\n"),
+    (r"<[^>]*>", ""),
+];
+
+/// Plain text rendering of an HTML body for the text/plain alternative:
+/// block elements become line breaks, links keep their target, tags and entities are resolved.
+pub fn html_to_text(html: &str) -> String {
+    static RULES: OnceLock<Vec<(Regex, &str)>> = OnceLock::new();
+    let rules = RULES.get_or_init(|| {
+        HTML_TO_TEXT_RULES.iter()
+            .map(|(pattern, replacement)| (Regex::new(pattern).expect("invalid html_to_text pattern"), *replacement))
+            .collect()
+    });
+
+    let mut text = html.to_string();
+    for (re, replacement) in rules {
+        text = re.replace_all(&text, *replacement).into_owned();
+    }
+    let text = html_escape::decode_html_entities(&text);
+
+    // Collapse runs of spaces and keep at most one blank line between blocks
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if line.is_empty() && lines.last().is_none_or(|l| l.is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+
+    lines.join("\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mail_parser::MimeHeaders;
+
+    fn attachment(filename: &str, content_id: Option<&str>) -> Attachment {
+        Attachment {
+            filename: filename.to_string(),
+            content: b"%PDF-1.4 test".to_vec(),
+            mime_type: "application/pdf".to_string(),
+            content_id: content_id.map(|c| c.to_string()),
+        }
+    }
+
+    #[test]
+    fn non_ascii_headers_and_filenames_round_trip_through_mail_parser() {
+        let subject = "Коммерческое предложение №42 — Überprüfung der Größe, très long sujet à plier";
+        let filename = "Коммерческое предложение для ООО «Ромашка» от 01.02.2024 (финальная версия).pdf";
+        let attachments = vec![attachment(filename, None)];
+
+        let raw = MimeBuilder::new("<p>Здравствуйте!</p>")
+            .header("Subject", subject)
+            .address_header("To", &["Jürgen Groß <jurgen@example.com>".to_string(), "\"Doe, John\" <john@example.com>".to_string()])
+            .attachments(&attachments)
+            .build();
+
+        // Nothing but ASCII goes on the wire, and no header line exceeds the RFC 5322 limit
+        assert!(raw.is_ascii());
+        assert!(String::from_utf8_lossy(&raw).lines().all(|l| l.len() <= 998));
+
+        let message = mail_parser::MessageParser::default().parse(&raw).unwrap();
+        assert_eq!(message.subject(), Some(subject));
+
+        let to: Vec<(Option<&str>, Option<&str>)> = message.to().unwrap().iter()
+            .map(|a| (a.name(), a.address()))
+            .collect();
+        assert_eq!(to, vec![
+            (Some("Jürgen Groß"), Some("jurgen@example.com")),
+            (Some("Doe, John"), Some("john@example.com")),
+        ]);
+
+        let attachment = message.attachment(0).unwrap();
+        assert_eq!(attachment.attachment_name(), Some(filename));
+        assert_eq!(attachment.contents(), b"%PDF-1.4 test");
+
+        assert_eq!(message.body_html(0).as_deref(), Some("<p>Здравствуйте!</p>"));
+        assert_eq!(message.body_text(0).as_deref(), Some("Здравствуйте!"));
+    }
+
+    #[test]
+    fn encode_header_leaves_ascii_alone_and_splits_long_values() {
+        assert_eq!(encode_header("Quote #42"), "Quote #42");
+
+        let encoded = encode_header(&"é".repeat(40));
+        let words: Vec<&str> = encoded.split("\r\n ").collect();
+        assert_eq!(words.len(), 2);
+        assert!(words.iter().all(|w| w.starts_with("=?UTF-8?B?") && w.ends_with("?=") && w.len() <= 75));
+    }
+
+    #[test]
+    fn html_to_text_keeps_structure_and_links() {
+        let html = "<html><head><title>T</title><style>p { color: red; }</style></head><body>\n\
+                    <h1>Quote&nbsp;#12</h1><p>Hello <b>Anna</b>,<br>see <a href=\"https://example.com/q?id=1&amp;x=2\">the quote</a>.</p>\
+                    <!-- hidden --><ul><li>One</li><li>Two &amp; three</li></ul><script>alert(1)</script></body></html>";
+
+        assert_eq!(
+            html_to_text(html),
+            "Quote #12\r\n\r\nHello Anna,\r\nsee the quote (https://example.com/q?id=1&x=2).\r\n\r\n- One\r\n- Two & three"
+        );
+    }
+
+    #[test]
+    fn html_to_text_collapses_whitespace() {
+        assert_eq!(html_to_text("  a \n\t b  <div></div><div></div><div>c</div>\n"), "a b\r\n\r\nc");
+        assert_eq!(html_to_text(""), "");
+    }
+
+    #[test]
+    fn wrap_base64_splits_lines_at_76_columns() {
+        let body = vec![0xAB; 120];
+        let wrapped = wrap_base64(&body);
+        let lines: Vec<&str> = wrapped.split("\r\n").collect();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[..2].iter().all(|l| l.len() == BASE64_LINE_LENGTH));
+        assert_eq!(lines[2].len(), 160 - 2 * BASE64_LINE_LENGTH);
+        assert_eq!(STANDARD.decode(lines.concat()).unwrap(), body);
+        assert_eq!(wrap_base64(b""), "");
+    }
+}
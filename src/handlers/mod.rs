@@ -1,6 +1,10 @@
 pub mod health;
 pub mod gmail;
 pub mod provider;
+pub mod address;
+pub mod pagination;
+pub mod query;
+pub mod mime;
 pub mod outlook;
 pub mod postmark;
 pub mod api;
@@ -2,7 +2,11 @@ use async_trait::async_trait;
 use reqwest::Client;
 use serde_json::json;
 use crate::error::AppError;
-use super::provider::{EmailProvider, CleanMessage, MessageSummary, SendMessageRequest, ListParams, Label, BatchModifyRequest};
+use regex::Regex;
+use std::sync::OnceLock;
+use super::pagination;
+use super::query::{GraphQuery, SearchQuery};
+use super::provider::{EmailProvider, CleanMessage, EmailAddress, MessageList, MessageFormat, MessageSummary, normalize_date, AttachmentSummary, SendMessageRequest, ListParams, Label, BatchModifyRequest, Thread, AttachmentContent, Draft, ReplyMode, ReplyRequest, BatchItemResult, LabelRequest, SYSTEM_LABELS, dedup_quoted_text, partition_batch_ids};
 
 pub struct OutlookProvider {
     client: Client,
@@ -12,54 +16,358 @@ impl OutlookProvider {
     pub fn new(client: Client) -> Self {
         Self { client }
     }
-}
 
-#[async_trait]
-impl EmailProvider for OutlookProvider {
-    async fn list_messages(&self, token: &str, params: ListParams) -> Result<serde_json::Value, AppError> {
-        let mut url = if let Some(label_id) = params.label_ids.as_deref() {
-            if label_id == "INBOX" {
-                "https://graph.microsoft.com/v1.0/me/mailFolders/inbox/messages".to_string()
-            } else if label_id == "SENT" {
-                "https://graph.microsoft.com/v1.0/me/mailFolders/sentitems/messages".to_string()
-            } else if label_id == "DRAFT" {
-                "https://graph.microsoft.com/v1.0/me/mailFolders/drafts/messages".to_string()
-            } else if label_id == "TRASH" {
-                "https://graph.microsoft.com/v1.0/me/mailFolders/deleteditems/messages".to_string()
-            } else {
-                format!("https://graph.microsoft.com/v1.0/me/mailFolders/{}/messages", label_id)
+    // GET a message resource with the body in the requested format ("html" or "text")
+    async fn fetch_message_json(&self, token: &str, url: &str, body_type: &str) -> Result<serde_json::Value, AppError> {
+        let res = self.client.get(url)
+            .bearer_auth(token)
+            .header("Prefer", format!("outlook.body-content-type=\"{}\"", body_type))
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+             // Fixed Point 10
+             return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
+        }
+
+        Ok(res.json().await?)
+    }
+
+    // A message with its HTML and text bodies and its attachments
+    async fn fetch_full_message(&self, token: &str, id: &str) -> Result<CleanMessage, AppError> {
+        let url = format!("https://graph.microsoft.com/v1.0/me/messages/{}", id);
+        let text_url = format!("{}?$select=body", url);
+
+        // Graph returns a single body type per request, so fetch HTML and text side by side
+        let (data, text, attachments) = tokio::try_join!(
+            self.fetch_message_json(token, &url, "html"),
+            self.fetch_message_json(token, &text_url, "text"),
+            self.fetch_attachments(token, id),
+        )?;
+
+        Ok(parse_full_message(&data, &text, attachments))
+    }
+
+    // Keep one entry per conversation with its count and participants. The listing is sorted
+    // newest first, so a conversation whose newest matching message is newer than what this page
+    // holds was already listed on an earlier page and is dropped here.
+    async fn collapse_conversations(&self, token: &str, folder: Option<&str>, graph: &GraphQuery, page: Vec<MessageSummary>) -> Vec<MessageSummary> {
+        use futures::stream::{self, StreamExt};
+        const CONCURRENCY_LIMIT: usize = 5;
+
+        let mut seen = std::collections::HashSet::new();
+        let firsts: Vec<MessageSummary> = page.into_iter()
+            .filter(|m| seen.insert(m.thread_id.clone()))
+            .collect();
+
+        let results: Vec<(MessageSummary, Result<Vec<serde_json::Value>, AppError>)> = stream::iter(firsts)
+            .map(|m| async move {
+                let conversation = self.fetch_conversation(token, folder, graph, &m.thread_id).await;
+                (m, conversation)
+            })
+            .buffered(CONCURRENCY_LIMIT)
+            .collect()
+            .await;
+
+        // $search results are ordered by relevance, so "newer" says nothing about earlier pages
+        let ordered_by_date = graph.search.is_none();
+
+        results.into_iter().filter_map(|(mut summary, conversation)| {
+            let conversation = match conversation {
+                Ok(c) => c,
+                Err(e) => {
+                    tracing::error!("Failed to fetch conversation {}: {:?}", summary.thread_id, e);
+                    return Some(summary);
+                }
+            };
+
+            // Both sides are normalized RFC 3339 UTC, so string order is chronological
+            let newest = conversation.iter().filter_map(|m| m["receivedDateTime"].as_str().and_then(normalize_date)).max();
+            if ordered_by_date && newest.is_some_and(|d| Some(d.as_str()) > summary.date.as_deref()) {
+                return None;
             }
+
+            let mut participants: Vec<EmailAddress> = Vec::new();
+            for sender in conversation.iter().filter_map(|m| graph_address(&m["from"])) {
+                if !participants.iter().any(|p| p.address.eq_ignore_ascii_case(&sender.address)) {
+                    participants.push(sender);
+                }
+            }
+
+            summary.messages_in_thread = Some(conversation.len() as u32);
+            summary.unread = conversation.iter().any(|m| !m["isRead"].as_bool().unwrap_or(true));
+            summary.has_attachments = conversation.iter().any(|m| m["hasAttachments"].as_bool().unwrap_or(false));
+            summary.participants = Some(participants);
+            Some(summary)
+        }).collect()
+    }
+
+    // Messages of a conversation that the listing itself would return (same folder and $filter),
+    // oldest first. $search cannot be combined with the conversationId filter, so a search
+    // counts the whole conversation within the folder.
+    async fn fetch_conversation(&self, token: &str, folder: Option<&str>, graph: &GraphQuery, conversation_id: &str) -> Result<Vec<serde_json::Value>, AppError> {
+        let mut filter = vec![format!("conversationId eq '{}'", conversation_id.replace('\'', "''"))];
+        filter.extend(listing_filter(folder, graph));
+        let mut next = Some(format!(
+            "{}?$filter={}&$select=from,receivedDateTime,isRead,hasAttachments&$top=100",
+            collection_url(folder),
+            urlencoding::encode(&filter.join(" and "))
+        ));
+
+        // Long conversations span several pages
+        let mut messages = Vec::new();
+        while let Some(url) = next {
+            let res = self.client.get(&url)
+                .bearer_auth(token)
+                .send()
+                .await?;
+
+            if !res.status().is_success() {
+                 return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
+            }
+
+            let data: serde_json::Value = res.json().await?;
+            messages.extend(data["value"].as_array().cloned().unwrap_or_default());
+            next = data["@odata.nextLink"].as_str().map(|s| s.to_string()).map(checked_next_link).transpose()?;
+        }
+        // Graph rejects $orderby combined with a conversationId filter, so we sort locally
+        messages.sort_by(|a, b| a["receivedDateTime"].as_str().cmp(&b["receivedDateTime"].as_str()));
+
+        Ok(messages)
+    }
+
+    // `label:` in the query picks the folder by canonical id, folder id or display name
+    async fn resolve_folder(&self, token: &str, label_ids: Option<&str>, query: &SearchQuery) -> Result<Option<String>, AppError> {
+        let label = match query.labels().as_slice() {
+            [] => return Ok(label_ids.map(|s| s.to_string())),
+            [label] => label.to_string(),
+            _ => return Err(AppError::BadRequest("Outlook messages live in a single folder, use one label:".to_string())),
+        };
+
+        // Canonical ids and Graph well-known names need no lookup, only custom folders walk the tree
+        let canonical = label.to_uppercase();
+        let well_known = WELL_KNOWN_FOLDERS.iter().find(|(_, name)| name.eq_ignore_ascii_case(&label));
+        let folder = if SYSTEM_LABELS.contains(&canonical.as_str()) {
+            canonical
+        } else if let Some((canonical, _)) = well_known {
+            canonical.to_string()
         } else {
-            "https://graph.microsoft.com/v1.0/me/messages".to_string()
+            self.list_labels(token, false).await?
+                .into_iter()
+                .find(|l| l.id == label || l.name.eq_ignore_ascii_case(&label))
+                .map(|l| l.id)
+                .ok_or_else(|| AppError::BadRequest(format!("Unknown folder '{}'", label)))?
         };
 
-        let mut query = Vec::new();
-        query.push("$select=id,subject,from,receivedDateTime,isRead,hasAttachments,bodyPreview,conversationId".to_string());
-        
-        // Fixed Point 12: Avoid duplicate $top
-        let top = params.max_results.unwrap_or(10);
-        query.push(format!("$top={}", top));
-        
-        if let Some(page) = params.page_number {
-            if page > 1 {
-                 let skip = (page - 1) * params.max_results.unwrap_or(10);
-                 query.push(format!("$skip={}", skip));
+        if label_ids.is_some_and(|l| l != folder) {
+            return Err(AppError::BadRequest("label: in q conflicts with label_ids".to_string()));
+        }
+
+        Ok(Some(folder))
+    }
+
+    // Every page of a folder collection, following @odata.nextLink
+    async fn fetch_folders(&self, token: &str, url: &str) -> Result<Vec<serde_json::Value>, AppError> {
+        let mut folders = Vec::new();
+        let mut next = Some(url.to_string());
+
+        while let Some(url) = next {
+            let res = self.client.get(&url)
+                .bearer_auth(token)
+                .send()
+                .await?;
+
+            if !res.status().is_success() {
+                 return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
             }
+
+            let data: serde_json::Value = res.json().await?;
+            folders.extend(data["value"].as_array().cloned().unwrap_or_default());
+            next = data["@odata.nextLink"].as_str().map(|s| s.to_string()).map(checked_next_link).transpose()?;
         }
 
-        if let Some(q) = &params.q {
-             query.push(format!("$search=\"{}\"", q));
+        Ok(folders)
+    }
+
+    // Folder id -> canonical id for the well-known folders that exist in this mailbox
+    async fn fetch_well_known_folders(&self, token: &str) -> Result<std::collections::HashMap<String, &'static str>, AppError> {
+        let requests: Vec<serde_json::Value> = WELL_KNOWN_FOLDERS.iter().map(|(canonical, name)| json!({
+            "id": canonical,
+            "method": "GET",
+            "url": format!("/me/mailFolders/{}?$select=id", name),
+        })).collect();
+
+        let res = self.client.post("https://graph.microsoft.com/v1.0/$batch")
+            .bearer_auth(token)
+            .json(&json!({ "requests": requests }))
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+             return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
         }
 
-        if !query.is_empty() {
-            url = format!("{}?{}", url, query.join("&"));
+        let data: serde_json::Value = res.json().await?;
+        let responses = data["responses"].as_array().cloned().unwrap_or_default();
+
+        // Mailboxes without an archive folder answer 404 for it, which is fine
+        Ok(responses.iter()
+            .filter(|r| r["status"].as_u64() == Some(200))
+            .filter_map(|r| {
+                let canonical = WELL_KNOWN_FOLDERS.iter().find(|(c, _)| r["id"].as_str() == Some(*c))?.0;
+                Some((r["body"]["id"].as_str()?.to_string(), canonical))
+            })
+            .collect())
+    }
+
+    async fn patch_message(&self, token: &str, id: &str, body: serde_json::Value) -> Result<(), AppError> {
+        let url = format!("https://graph.microsoft.com/v1.0/me/messages/{}", id);
+
+        let res = self.client.patch(&url)
+            .bearer_auth(token)
+            .json(&body)
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+             return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
+        }
+
+        Ok(())
+    }
+
+    // Move a message to a folder id or well-known name (inbox, archive, deleteditems, ...)
+    async fn move_message(&self, token: &str, id: &str, destination: &str) -> Result<(), AppError> {
+        let url = format!("https://graph.microsoft.com/v1.0/me/messages/{}/move", id);
+
+        let res = self.client.post(&url)
+            .bearer_auth(token)
+            .json(&json!({ "destinationId": destination }))
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+             return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
+        }
+
+        Ok(())
+    }
+
+    // The account's own address with the requested display name; Exchange may still show the mailbox name
+    async fn sender(&self, token: &str, req: &SendMessageRequest) -> Result<Option<EmailAddress>, AppError> {
+        let Some(from_name) = req.from_name.as_deref() else {
+            return Ok(None);
+        };
+        let profile = self.get_profile(token).await?;
+        Ok(Some(EmailAddress::from_parts(Some(from_name), Some(&profile.email))))
+    }
+
+    // Only the @odata.nextLink following `next_link` (or the first page), used to jump to a page number
+    async fn fetch_next_link(&self, token: &str, first_url: &str, next_link: Option<String>) -> Result<Option<String>, AppError> {
+        let url = match next_link {
+            Some(link) => checked_next_link(link)?,
+            None => first_url.to_string(),
+        };
+
+        let res = self.client.get(&url)
+            .bearer_auth(token)
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+             return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
         }
 
+        let data: serde_json::Value = res.json().await?;
+        Ok(data["@odata.nextLink"].as_str().map(|s| s.to_string()))
+    }
+
+    // List the attachments of a message without downloading their content
+    async fn fetch_attachments(&self, token: &str, message_id: &str) -> Result<Vec<AttachmentSummary>, AppError> {
+        let url = format!(
+            "https://graph.microsoft.com/v1.0/me/messages/{}/attachments?$select=id,name,contentType,size,isInline",
+            message_id
+        );
+
         let res = self.client.get(&url)
             .bearer_auth(token)
             .send()
             .await?;
 
+        if !res.status().is_success() {
+             return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
+        }
+
+        let data: serde_json::Value = res.json().await?;
+        let items = data["value"].as_array().cloned().unwrap_or_default();
+
+        // contentId only exists on fileAttachment and cannot be $selected on the base type,
+        // so it is read per inline attachment (typically small images referenced as cid:)
+        let summaries = futures::future::join_all(items.iter().map(|a| async move {
+            let id = a["id"].as_str().unwrap_or("").to_string();
+            let content_id = if a["isInline"].as_bool().unwrap_or(false) {
+                self.fetch_content_id(token, message_id, &id).await
+            } else {
+                None
+            };
+
+            parse_attachment(a, content_id)
+        })).await;
+
+        Ok(summaries)
+    }
+
+    async fn fetch_content_id(&self, token: &str, message_id: &str, attachment_id: &str) -> Option<String> {
+        let url = format!(
+            "https://graph.microsoft.com/v1.0/me/messages/{}/attachments/{}",
+            message_id, attachment_id
+        );
+
+        let res = self.client.get(&url).bearer_auth(token).send().await.ok()?;
+        if !res.status().is_success() {
+            tracing::warn!("Failed to read contentId of inline attachment {}: {}", attachment_id, res.status());
+            return None;
+        }
+
+        let data: serde_json::Value = res.json().await.ok()?;
+        data["contentId"].as_str().map(|s| s.to_string())
+    }
+}
+
+#[async_trait]
+impl EmailProvider for OutlookProvider {
+    async fn list_messages(&self, token: &str, params: ListParams) -> Result<MessageList, AppError> {
+        let query = params.q.as_deref().map(SearchQuery::parse).transpose()?.unwrap_or_default();
+        let graph = query.to_graph()?;
+        let folder = self.resolve_folder(token, params.label_ids.as_deref(), &query).await?;
+
+        if folder.as_deref() == Some("STARRED") && graph.search.is_some() {
+            // $search cannot be combined with the flag $filter
+            return Err(AppError::BadRequest("Searching within STARRED is not supported for Outlook".to_string()));
+        }
+        let first_url = first_page_url(&params, folder.as_deref(), &graph);
+
+        // Pagination Logic: Graph pages with @odata.nextLink, a full URL carrying a $skiptoken.
+        // It is the upstream token wrapped in our cursor, like Gmail's nextPageToken.
+        let resolved = pagination::resolve("outlook", &params, |next_link| {
+            self.fetch_next_link(token, &first_url, next_link)
+        }).await?;
+        let Some(resolved) = resolved else {
+            return Ok(pagination::not_found(&params));
+        };
+        let page_num = resolved.page;
+
+        let url = match resolved.upstream_token {
+            Some(link) => checked_next_link(link)?,
+            None => first_url.clone(),
+        };
+
+        // The well-known folder ids turn parentFolderId into canonical label ids
+        let (res, well_known) = tokio::try_join!(
+            async { Ok::<_, AppError>(self.client.get(&url).bearer_auth(token).send().await?) },
+            self.fetch_well_known_folders(token),
+        )?;
+
         if !res.status().is_success() {
              // Fixed Point 10: Specific Outlook error
              return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
@@ -69,125 +377,117 @@ impl EmailProvider for OutlookProvider {
         
         let messages_raw = data["value"].as_array().ok_or_else(|| anyhow::anyhow!("Messages not found in response"))?;
         
-        let summaries: Vec<MessageSummary> = messages_raw.iter().map(|m| {
-            MessageSummary {
-                id: m["id"].as_str().unwrap_or("").to_string(),
-                thread_id: m["conversationId"].as_str().unwrap_or("").to_string(),
-                snippet: m["bodyPreview"].as_str().unwrap_or("").to_string(),
-                subject: m["subject"].as_str().map(|s| s.to_string()),
-                from: m["from"]["emailAddress"]["address"].as_str()
-                    .or_else(|| m["from"]["emailAddress"]["name"].as_str())
-                    .map(|s| s.to_string()),
-                date: m["receivedDateTime"].as_str().map(|s| s.to_string()),
-                unread: !m["isRead"].as_bool().unwrap_or(true),
-                has_attachments: m["hasAttachments"].as_bool().unwrap_or(false),
-                messages_in_thread: None,
+        let mut summaries: Vec<MessageSummary> = messages_raw.iter().map(parse_summary).collect();
+        for label in summaries.iter_mut().flat_map(|s| s.label_ids.iter_mut()) {
+            if let Some(canonical) = well_known.get(label.as_str()) {
+                *label = canonical.to_string();
             }
-        }).collect();
+        }
+
+        if params.collapse_threads.unwrap_or(false) {
+            summaries = self.collapse_conversations(token, folder.as_deref(), &graph, summaries).await;
+        }
         
-        Ok(json!({
-            "messages": summaries,
-            "@odata.nextLink": data["@odata.nextLink"]
-        }))
+        let next_page_token = data["@odata.nextLink"]
+            .as_str()
+            .map(|link| pagination::encode("outlook", &params, page_num + 1, link))
+            .transpose()?;
+        
+        // @odata.count is only available without $search
+        Ok(MessageList::new(summaries, page_num, next_page_token, data["@odata.count"].as_u64()))
     }
 
-    async fn get_message(&self, token: &str, id: &str) -> Result<CleanMessage, AppError> {
-        let url = format!("https://graph.microsoft.com/v1.0/me/messages/{}", id);
-        
-        let res = self.client.get(&url)
+    async fn get_message(&self, token: &str, id: &str, _format: MessageFormat) -> Result<CleanMessage, AppError> {
+        // Graph always returns structured messages, there is no raw mode to opt into
+        self.fetch_full_message(token, id).await
+    }
+
+    async fn get_thread(&self, token: &str, thread_id: &str) -> Result<Thread, AppError> {
+        use futures::stream::{self, StreamExt, TryStreamExt};
+        const CONCURRENCY_LIMIT: usize = 5;
+
+        // Graph rejects $orderby combined with a conversationId filter, so we sort locally
+        let filter = format!("conversationId eq '{}'", thread_id.replace('\'', "''"));
+        let mut next = Some(format!(
+            "https://graph.microsoft.com/v1.0/me/messages?$filter={}&$select=id&$top=50",
+            urlencoding::encode(&filter)
+        ));
+
+        // Long conversations span several pages
+        let mut ids: Vec<String> = Vec::new();
+        while let Some(url) = next {
+            let res = self.client.get(&url)
+                .bearer_auth(token)
+                .send()
+                .await?;
+
+            if !res.status().is_success() {
+                 return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
+            }
+
+            let data: serde_json::Value = res.json().await?;
+            let messages_raw = data["value"].as_array().ok_or_else(|| anyhow::anyhow!("Messages not found in response"))?;
+            ids.extend(messages_raw.iter().filter_map(|m| m["id"].as_str().map(|s| s.to_string())));
+            next = data["@odata.nextLink"].as_str().map(|s| s.to_string()).map(checked_next_link).transpose()?;
+        }
+
+        // Same content as get_message (both bodies and attachments), like Gmail threads
+        let mut messages: Vec<CleanMessage> = stream::iter(ids)
+            .map(|id| async move { self.fetch_full_message(token, &id).await })
+            .buffered(CONCURRENCY_LIMIT)
+            .try_collect()
+            .await?;
+        // receivedDateTime is always ISO 8601 UTC, so lexical order is chronological
+        messages.sort_by(|a, b| a.date.cmp(&b.date));
+
+        dedup_quoted_text(&mut messages);
+
+        Ok(Thread {
+            id: thread_id.to_string(),
+            messages,
+        })
+    }
+    
+    async fn get_attachment(&self, token: &str, message_id: &str, attachment_id: &str) -> Result<AttachmentContent, AppError> {
+        let url = format!(
+            "https://graph.microsoft.com/v1.0/me/messages/{}/attachments/{}",
+            message_id, attachment_id
+        );
+
+        // Metadata first (without contentBytes), then stream the raw bytes from $value
+        let res = self.client.get(format!("{}?$select=name,contentType,size", url))
             .bearer_auth(token)
-            .header("Prefer", "outlook.body-content-type=\"text\"") 
             .send()
             .await?;
 
         if !res.status().is_success() {
-             // Fixed Point 10
              return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
         }
-        
-        let data: serde_json::Value = res.json().await?;
-        
-        // Parse Outlook JSON to CleanMessage
-        let subject = data["subject"].as_str().map(|s| s.to_string());
-        
-        // Prioritize address for "from" if it's for an email field
-        let from = data["from"]["emailAddress"]["address"].as_str()
-            .or_else(|| data["from"]["emailAddress"]["name"].as_str())
-            .map(|s| s.to_string());
-            
-        let date = data["receivedDateTime"].as_str().map(|s| s.to_string());
-        let snippet = data["bodyPreview"].as_str().unwrap_or("").to_string();
-        
-        // Extract recipients
-        let to = data["toRecipients"].as_array().map(|recipients| {
-            recipients.iter()
-                .filter_map(|r| r["emailAddress"]["address"].as_str().map(|s| s.to_string()))
-                .collect::<Vec<String>>()
-        });
 
-        let cc = data["ccRecipients"].as_array().map(|recipients| {
-            recipients.iter()
-                .filter_map(|r| r["emailAddress"]["address"].as_str().map(|s| s.to_string()))
-                .collect::<Vec<String>>()
-        });
+        let meta: serde_json::Value = res.json().await?;
+
+        let res = self.client.get(format!("{}/$value", url))
+            .bearer_auth(token)
+            .send()
+            .await?;
 
-        Ok(CleanMessage {
-            id: id.to_string(),
-            subject,
-            from,
-            to,
-            cc,
-            date,
-            snippet,
-            body_text: data["body"]["content"].as_str().map(|s| s.to_string()),
-            body_html: None, 
-            attachments: vec![],
+        if !res.status().is_success() {
+             return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
+        }
+
+        Ok(AttachmentContent {
+            filename: meta["name"].as_str().unwrap_or("unnamed").to_string(),
+            mime_type: meta["contentType"].as_str().unwrap_or("application/octet-stream").to_string(),
+            size: res.content_length(),
+            body: axum::body::Body::from_stream(res.bytes_stream()),
         })
     }
     
     async fn send_message(&self, token: &str, req: SendMessageRequest) -> Result<serde_json::Value, AppError> {
          let url = "https://graph.microsoft.com/v1.0/me/sendMail";
-         
-         let recipients: Vec<serde_json::Value> = req.to.iter().map(|email| {
-             json!({
-                 "emailAddress": {
-                     "address": email
-                 }
-             })
-         }).collect();
-
-         let cc_recipients: Vec<serde_json::Value> = req.cc.unwrap_or_default().iter().map(|email| {
-             json!({
-                 "emailAddress": {
-                     "address": email
-                 }
-             })
-         }).collect();
-
-         let mut attachments_json = vec![];
-         if let Some(attachments) = req.attachments {
-             use base64::{Engine as _, engine::general_purpose};
-             for att in attachments {
-                 attachments_json.push(json!({
-                     "@odata.type": "#microsoft.graph.fileAttachment",
-                     "name": att.filename,
-                     "contentType": att.mime_type,
-                     "contentBytes": general_purpose::STANDARD.encode(&att.content) 
-                 }));
-             }
-         }
 
          let body = json!({
-             "message": {
-                 "subject": req.subject,
-                 "body": {
-                     "contentType": "HTML",
-                     "content": req.body
-                 },
-                 "toRecipients": recipients,
-                 "ccRecipients": cc_recipients,
-                 "attachments": attachments_json
-             },
+             "message": build_message_json(&req, self.sender(token, &req).await?.as_ref()),
              "saveToSentItems": "true"
          });
 
@@ -205,97 +505,548 @@ impl EmailProvider for OutlookProvider {
          Ok(json!({"status": "sent"}))
     }
 
-    async fn list_labels(&self, token: &str) -> Result<Vec<Label>, AppError> {
-        let url = "https://graph.microsoft.com/v1.0/me/mailFolders?$top=99";
+    async fn reply(&self, token: &str, message_id: &str, mode: ReplyMode, req: ReplyRequest) -> Result<serde_json::Value, AppError> {
+        let action = match mode {
+            ReplyMode::Reply => "createReply",
+            ReplyMode::ReplyAll => "createReplyAll",
+            ReplyMode::Forward => "createForward",
+        };
 
-        let res = self.client
-            .get(url)
+        if mode == ReplyMode::Forward && req.to.as_deref().unwrap_or_default().is_empty() {
+            return Err(AppError::BadRequest("Forward requires at least one recipient in 'to'".to_string()));
+        }
+
+        // Graph builds the draft in the same conversation, with subject prefix, quoted
+        // original and (for forwards) the original attachments
+        let url = format!("https://graph.microsoft.com/v1.0/me/messages/{}/{}", message_id, action);
+        let res = self.client.post(&url)
+            .bearer_auth(token)
+            .json(&json!({}))
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+             return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
+        }
+
+        let draft: serde_json::Value = res.json().await?;
+        let draft_id = draft["id"].as_str().ok_or_else(|| anyhow::anyhow!("Reply draft has no id"))?.to_string();
+
+        // Put our HTML on top of the quoted original
+        static BODY_TAG: OnceLock<Regex> = OnceLock::new();
+        let body_tag = BODY_TAG.get_or_init(|| Regex::new(r"(?i)<body[^>]*>").expect("invalid body tag pattern"));
+        let quoted = draft["body"]["content"].as_str().unwrap_or("");
+        let content = match body_tag.find(quoted) {
+            Some(m) => format!("{}{}{}", &quoted[..m.end()], req.body, &quoted[m.end()..]),
+            None => format!("{}{}", req.body, quoted),
+        };
+
+        let mut patch = json!({
+            "body": {
+                "contentType": "HTML",
+                "content": content
+            }
+        });
+        if let Some(to) = &req.to {
+            patch["toRecipients"] = json!(recipients_json(to));
+        }
+        if let Some(cc) = &req.cc {
+            // Keep the Cc list Graph computed for reply-all and add ours
+            let mut cc_recipients = draft["ccRecipients"].as_array().cloned().unwrap_or_default();
+            cc_recipients.extend(recipients_json(cc));
+            patch["ccRecipients"] = json!(cc_recipients);
+        }
+
+        let draft_url = format!("https://graph.microsoft.com/v1.0/me/messages/{}", draft_id);
+        let res = self.client.patch(&draft_url)
+            .bearer_auth(token)
+            .json(&patch)
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+             return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
+        }
+
+        for attachment in attachments_json(req.attachments.as_deref().unwrap_or_default()) {
+            let res = self.client.post(format!("{}/attachments", draft_url))
+                .bearer_auth(token)
+                .json(&attachment)
+                .send()
+                .await?;
+
+            if !res.status().is_success() {
+                 return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
+            }
+        }
+
+        self.send_draft(token, &draft_id).await
+    }
+
+    async fn list_drafts(&self, token: &str, max_results: Option<u32>) -> Result<Vec<Draft>, AppError> {
+        let url = format!(
+            "https://graph.microsoft.com/v1.0/me/mailFolders/drafts/messages?$select=id,subject,toRecipients,lastModifiedDateTime,bodyPreview,conversationId&$orderby=lastModifiedDateTime desc&$top={}",
+            max_results.unwrap_or(10)
+        );
+
+        let res = self.client.get(&url)
             .bearer_auth(token)
             .send()
             .await?;
 
         if !res.status().is_success() {
-            // Fixed Point 10
-            return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
+             return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
         }
 
         let data: serde_json::Value = res.json().await?;
-        let folders = data["value"].as_array().ok_or_else(|| anyhow::anyhow!("Folders not found"))?;
-
-        let labels = folders
-            .iter()
-            .map(|f| Label {
-                id: f["id"].as_str().unwrap_or("").to_string(),
-                name: f["displayName"].as_str().unwrap_or("").to_string(),
-                label_type: Some("user".to_string()), // Simplified
-            })
-            .collect();
+        let messages_raw = data["value"].as_array().ok_or_else(|| anyhow::anyhow!("Drafts not found in response"))?;
+
+        Ok(messages_raw.iter().map(parse_draft).collect())
+    }
+
+    async fn create_draft(&self, token: &str, req: SendMessageRequest) -> Result<Draft, AppError> {
+        // A message created directly under /me/messages lands in the Drafts folder
+        let res = self.client.post("https://graph.microsoft.com/v1.0/me/messages")
+            .bearer_auth(token)
+            .json(&build_message_json(&req, self.sender(token, &req).await?.as_ref()))
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+             return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
+        }
+
+        let data: serde_json::Value = res.json().await?;
+        Ok(parse_draft(&data))
+    }
+
+    async fn update_draft(&self, token: &str, draft_id: &str, req: SendMessageRequest) -> Result<Draft, AppError> {
+        let url = format!("https://graph.microsoft.com/v1.0/me/messages/{}", draft_id);
+
+        if req.headers.as_ref().is_some_and(|h| !h.is_empty()) {
+            return Err(AppError::BadRequest("Outlook only accepts custom headers when a draft is created".to_string()));
+        }
+
+        // Graph does not accept attachments in PATCH, they are added one by one below
+        let mut message = build_message_json(&req, self.sender(token, &req).await?.as_ref());
+        if let Some(m) = message.as_object_mut() {
+            m.remove("attachments");
+        }
+        let attachments = attachments_json(req.attachments.as_deref().unwrap_or_default());
+
+        let res = self.client.patch(&url)
+            .bearer_auth(token)
+            .json(&message)
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+             return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
+        }
+
+        let data: serde_json::Value = res.json().await?;
+
+        // Like the Gmail draft, the request's attachments replace the existing ones
+        let res = self.client.get(format!("{}/attachments?$select=id", url))
+            .bearer_auth(token)
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+             return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
+        }
+
+        let existing: serde_json::Value = res.json().await?;
+        for id in existing["value"].as_array().into_iter().flatten().filter_map(|a| a["id"].as_str()) {
+            let res = self.client.delete(format!("{}/attachments/{}", url, id))
+                .bearer_auth(token)
+                .send()
+                .await?;
+
+            if !res.status().is_success() {
+                 return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
+            }
+        }
+
+        for attachment in attachments {
+            let res = self.client.post(format!("{}/attachments", url))
+                .bearer_auth(token)
+                .json(&attachment)
+                .send()
+                .await?;
+
+            if !res.status().is_success() {
+                 return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
+            }
+        }
+
+        Ok(parse_draft(&data))
+    }
+
+    async fn send_draft(&self, token: &str, draft_id: &str) -> Result<serde_json::Value, AppError> {
+        let url = format!("https://graph.microsoft.com/v1.0/me/messages/{}/send", draft_id);
+
+        let res = self.client.post(&url)
+            .bearer_auth(token)
+            .header("Content-Length", "0")
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+             return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
+        }
+
+        Ok(json!({"status": "sent"}))
+    }
+
+    async fn mark_read(&self, token: &str, id: &str, read: bool) -> Result<(), AppError> {
+        self.patch_message(token, id, json!({ "isRead": read })).await
+    }
+
+    async fn set_starred(&self, token: &str, id: &str, starred: bool) -> Result<(), AppError> {
+        let status = if starred { "flagged" } else { "notFlagged" };
+        self.patch_message(token, id, json!({ "flag": { "flagStatus": status } })).await
+    }
+
+    async fn archive(&self, token: &str, id: &str) -> Result<(), AppError> {
+        self.move_message(token, id, "archive").await
+    }
+
+    async fn trash(&self, token: &str, id: &str) -> Result<(), AppError> {
+        self.move_message(token, id, "deleteditems").await
+    }
+
+    async fn restore(&self, token: &str, id: &str) -> Result<(), AppError> {
+        // Graph does not remember the original folder, restored mail goes back to the inbox
+        self.move_message(token, id, "inbox").await
+    }
+
+    async fn delete_message(&self, token: &str, id: &str) -> Result<(), AppError> {
+        // A plain DELETE only moves the message to Deleted Items
+        let url = format!("https://graph.microsoft.com/v1.0/me/messages/{}/permanentDelete", id);
+
+        let res = self.client.post(&url)
+            .bearer_auth(token)
+            .header("Content-Length", "0")
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+             return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
+        }
+
+        Ok(())
+    }
+
+    async fn list_labels(&self, token: &str, _with_counts: bool) -> Result<Vec<Label>, AppError> {
+        const CONCURRENCY_LIMIT: usize = 5;
+        use futures::stream::{self, StreamExt, TryStreamExt};
+
+        let root_url = format!("https://graph.microsoft.com/v1.0/me/mailFolders?{}", FOLDER_QUERY);
+        let (well_known, mut level) = tokio::try_join!(
+            self.fetch_well_known_folders(token),
+            self.fetch_folders(token, &root_url),
+        )?;
+
+        // Walk the hierarchy one level at a time through childFolders
+        let mut folders = Vec::new();
+        while !level.is_empty() {
+            let parents: Vec<String> = level.iter()
+                .filter(|f| f["childFolderCount"].as_u64().unwrap_or(0) > 0)
+                .filter_map(|f| f["id"].as_str().map(|s| s.to_string()))
+                .collect();
+            folders.append(&mut level);
+
+            let children: Vec<Vec<serde_json::Value>> = stream::iter(parents)
+                .map(|id| async move {
+                    let url = format!("https://graph.microsoft.com/v1.0/me/mailFolders/{}/childFolders?{}", id, FOLDER_QUERY);
+                    self.fetch_folders(token, &url).await
+                })
+                .buffered(CONCURRENCY_LIMIT)
+                .try_collect()
+                .await?;
+            level = children.into_iter().flatten().collect();
+        }
+
+        // Root folders point at the hidden msgfolderroot, which is not part of the list
+        let ids: std::collections::HashSet<&str> = folders.iter().filter_map(|f| f["id"].as_str()).collect();
+        let canonical = |id: &str| well_known.get(id).map(|c| c.to_string()).unwrap_or_else(|| id.to_string());
+
+        let mut labels: Vec<Label> = folders.iter().map(|f| {
+            let mut label = parse_folder(f);
+            if well_known.contains_key(label.id.as_str()) {
+                label.label_type = Some("system".to_string());
+            }
+            label.id = canonical(&label.id);
+            label.parent_id = f["parentFolderId"].as_str().filter(|p| ids.contains(p)).map(canonical);
+            label
+        }).collect();
+
+        labels.push(Label::virtual_system("STARRED", "Flagged"));
 
         Ok(labels)
     }
 
-    async fn batch_modify_labels(&self, token: &str, req: BatchModifyRequest) -> Result<(), AppError> {
-        
-        // Moving to folder in Outlook is done per-message via POST /messages/{id}/move
-        // We only support moving to a SINGLE folder (the first one in add_label_ids)
-        // We clone getting the first folder ID to avoid borrowing issues while iterating.
-        let target_folder = req.add_label_ids.as_ref().and_then(|ids| ids.first()).cloned();
-        
-        if let Some(folder_id) = target_folder {
-            let mut tasks = Vec::new();
-            
-            for message_id in req.ids {
-                let client = self.client.clone();
-                let token = token.to_string();
-                let folder_id = folder_id.clone();
-                
-                tasks.push(tokio::spawn(async move {
-                    let url = format!("https://graph.microsoft.com/v1.0/me/messages/{}/move", message_id);
-                    let body = json!({
-                        "destinationId": folder_id
-                    });
+    async fn create_label(&self, token: &str, req: LabelRequest) -> Result<Label, AppError> {
+        if req.color.is_some() {
+            return Err(AppError::BadRequest("Folder colors are not supported for Outlook".to_string()));
+        }
+        let name = req.name.as_deref().map(str::trim).unwrap_or_default();
+        if name.is_empty() {
+            return Err(AppError::BadRequest("Folder name is required".to_string()));
+        }
+
+        let url = match &req.parent_id {
+            Some(parent_id) => format!("https://graph.microsoft.com/v1.0/me/mailFolders/{}/childFolders", folder_ref(parent_id)),
+            None => "https://graph.microsoft.com/v1.0/me/mailFolders".to_string(),
+        };
+
+        let res = self.client.post(&url)
+            .bearer_auth(token)
+            .json(&json!({ "displayName": name }))
+            .send()
+            .await?;
 
-                    client.post(&url)
-                        .bearer_auth(token)
-                        .json(&body)
-                        .send()
-                        .await
-                }));
+        if !res.status().is_success() {
+             return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
+        }
+
+        let data: serde_json::Value = res.json().await?;
+        Ok(parse_folder(&data))
+    }
+
+    async fn update_label(&self, token: &str, label_id: &str, req: LabelRequest) -> Result<Label, AppError> {
+        if req.color.is_some() {
+            return Err(AppError::BadRequest("Folder colors are not supported for Outlook".to_string()));
+        }
+
+        let mut folder_id = folder_ref(label_id).to_string();
+
+        // Re-parenting is a move, which returns the folder (possibly under a new id)
+        if let Some(parent_id) = &req.parent_id {
+            let url = format!("https://graph.microsoft.com/v1.0/me/mailFolders/{}/move", folder_id);
+
+            let res = self.client.post(&url)
+                .bearer_auth(token)
+                .json(&json!({ "destinationId": folder_ref(parent_id) }))
+                .send()
+                .await?;
+
+            if !res.status().is_success() {
+                 return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
             }
 
-            // Execute all move requests in parallel
-            let results = futures::future::join_all(tasks).await;
-
-            // Check for errors and log them, but don't fail the whole batch
-            // This implements "Best Effort" strategy
-            let mut errors_count = 0;
-            for res in results {
-                match res {
-                    Ok(Ok(response)) => {
-                        if !response.status().is_success() {
-                            let status = response.status();
-                            let text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-                            tracing::error!("Outlook API message move partial failure: {} - {}", status, text);
-                            errors_count += 1;
-                        }
-                    },
-                    Ok(Err(e)) => {
-                        tracing::error!("Outlook API message move reqwest error: {}", e);
-                        errors_count += 1;
-                    }, 
-                    Err(e) => {
-                         tracing::error!("Task join error in batch move: {}", e);
-                         errors_count += 1;
-                    }, 
+            let data: serde_json::Value = res.json().await?;
+            if let Some(id) = data["id"].as_str() {
+                folder_id = id.to_string();
+            }
+        }
+
+        let url = format!("https://graph.microsoft.com/v1.0/me/mailFolders/{}", folder_id);
+
+        let request = match req.name.as_deref().map(str::trim).filter(|n| !n.is_empty()) {
+            Some(name) => self.client.patch(&url).json(&json!({ "displayName": name })),
+            None => self.client.get(&url),
+        };
+
+        let res = request
+            .bearer_auth(token)
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+             return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
+        }
+
+        let data: serde_json::Value = res.json().await?;
+        Ok(parse_folder(&data))
+    }
+
+    async fn delete_label(&self, token: &str, label_id: &str) -> Result<(), AppError> {
+        let url = format!("https://graph.microsoft.com/v1.0/me/mailFolders/{}", folder_ref(label_id));
+
+        let res = self.client.delete(&url)
+            .bearer_auth(token)
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+             return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
+        }
+
+        Ok(())
+    }
+
+    async fn batch_modify_labels(&self, token: &str, req: BatchModifyRequest) -> Result<Vec<BatchItemResult>, AppError> {
+        // Graph accepts at most 20 requests per $batch call
+        const GRAPH_BATCH_LIMIT: usize = 20;
+
+        let add = req.add_label_ids.unwrap_or_default();
+        let remove = req.remove_label_ids.unwrap_or_default();
+
+        // STARRED and UNREAD are message properties on Outlook, everything else is a folder
+        let mut patch = serde_json::Map::new();
+        if add.iter().any(|l| l == "STARRED") {
+            patch.insert("flag".to_string(), json!({ "flagStatus": "flagged" }));
+        } else if remove.iter().any(|l| l == "STARRED") {
+            patch.insert("flag".to_string(), json!({ "flagStatus": "notFlagged" }));
+        }
+        if add.iter().any(|l| l == "UNREAD") {
+            patch.insert("isRead".to_string(), json!(false));
+        } else if remove.iter().any(|l| l == "UNREAD") {
+            patch.insert("isRead".to_string(), json!(true));
+        }
+
+        // A message lives in exactly one folder, so only the first folder in add_label_ids is used.
+        // Without one, removing INBOX archives and removing ARCHIVE moves back to the inbox, as on Gmail.
+        let is_folder = |l: &&String| *l != "STARRED" && *l != "UNREAD";
+        let target_folder = add.iter().find(is_folder).map(|l| folder_ref(l).to_string())
+            .or_else(|| remove.iter().any(|l| l == "INBOX").then(|| folder_ref("ARCHIVE").to_string()))
+            .or_else(|| remove.iter().any(|l| l == "ARCHIVE").then(|| folder_ref("INBOX").to_string()));
+
+        // Leaving any other folder needs a destination, nothing is applied rather than half of it
+        if let Some(folder) = remove.iter().find(is_folder).filter(|_| target_folder.is_none()) {
+            let reason = format!("Outlook cannot remove a message from folder '{}' without moving it, put the destination in add_label_ids", folder);
+            return Ok(req.ids.into_iter().map(|id| BatchItemResult::skipped(id, reason.clone())).collect());
+        }
+
+        if patch.is_empty() && target_folder.is_none() {
+            return Ok(req.ids.into_iter()
+                .map(|id| BatchItemResult::skipped(id, "Nothing to change: Outlook applies add_label_ids folders, STARRED and UNREAD"))
+                .collect());
+        }
+
+        let (ids, mut results) = partition_batch_ids(req.ids);
+        let ops_per_message = usize::from(!patch.is_empty()) + usize::from(target_folder.is_some());
+
+        for chunk in ids.chunks(GRAPH_BATCH_LIMIT / ops_per_message) {
+            // Sub-request ids are "<position>-patch" / "<position>-move"; the move runs last since it changes the message id
+            let mut requests = Vec::new();
+            for (i, message_id) in chunk.iter().enumerate() {
+                if !patch.is_empty() {
+                    requests.push(json!({
+                        "id": format!("{}-patch", i),
+                        "method": "PATCH",
+                        "url": format!("/me/messages/{}", message_id),
+                        "headers": { "Content-Type": "application/json" },
+                        "body": patch,
+                    }));
+                }
+                if let Some(folder_id) = &target_folder {
+                    let mut request = json!({
+                        "id": format!("{}-move", i),
+                        "method": "POST",
+                        "url": format!("/me/messages/{}/move", message_id),
+                        "headers": { "Content-Type": "application/json" },
+                        "body": { "destinationId": folder_id },
+                    });
+                    if !patch.is_empty() {
+                        request["dependsOn"] = json!([format!("{}-patch", i)]);
+                    }
+                    requests.push(request);
                 }
             }
-            
-            if errors_count > 0 {
-                tracing::warn!("Batch move completed with {} errors", errors_count);
+
+            let res = self.client.post("https://graph.microsoft.com/v1.0/$batch")
+                .bearer_auth(token)
+                .json(&json!({ "requests": requests }))
+                .send()
+                .await;
+
+            let data: serde_json::Value = match res {
+                Ok(res) if res.status().is_success() => res.json().await.unwrap_or_default(),
+                Ok(res) => {
+                    let status = res.status();
+                    let text = res.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+                    tracing::error!("Outlook API $batch error: {} - {}", status, text);
+                    let reason = format!("Outlook API Error {}: {}", status, text);
+                    results.extend(chunk.iter().cloned().map(|id| BatchItemResult::failed(id, reason.clone())));
+                    continue;
+                }
+                Err(e) => {
+                    tracing::error!("Outlook API $batch reqwest error: {}", e);
+                    results.extend(chunk.iter().cloned().map(|id| BatchItemResult::failed(id, e.to_string())));
+                    continue;
+                }
+            };
+
+            let responses = data["responses"].as_array().cloned().unwrap_or_default();
+            for (i, message_id) in chunk.iter().enumerate() {
+                let prefix = format!("{}-", i);
+
+                // The first failing sub-request of this message, if any
+                let failure = requests.iter()
+                    .filter_map(|r| r["id"].as_str())
+                    .filter(|id| id.starts_with(&prefix))
+                    .find_map(|id| {
+                        let response = responses.iter().find(|r| r["id"].as_str() == Some(id));
+                        let status = response.and_then(|r| r["status"].as_u64()).unwrap_or(0);
+                        if (200..300).contains(&status) {
+                            return None;
+                        }
+                        Some(response
+                            .and_then(|r| r["body"]["error"]["message"].as_str())
+                            .map(|m| format!("Outlook API Error {}: {}", status, m))
+                            .unwrap_or_else(|| format!("Outlook API Error {}", status)))
+                    });
+
+                match failure {
+                    None => results.push(BatchItemResult::succeeded(message_id.clone())),
+                    Some(reason) => {
+                        tracing::error!("Outlook API batch modify partial failure: {}", reason);
+                        results.push(BatchItemResult::failed(message_id.clone(), reason));
+                    }
+                }
             }
         }
 
-        Ok(())
+        Ok(results)
+    }
+
+    async fn get_raw_message(&self, token: &str, id: &str) -> Result<Vec<u8>, AppError> {
+        let url = format!("https://graph.microsoft.com/v1.0/me/messages/{}/$value", id);
+
+        let res = self.client.get(&url)
+            .bearer_auth(token)
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+             return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
+        }
+
+        Ok(res.bytes().await?.to_vec())
+    }
+
+    async fn import_message(&self, token: &str, raw: Vec<u8>, label_id: &str) -> Result<serde_json::Value, AppError> {
+        if label_id.eq_ignore_ascii_case("STARRED") {
+            return Err(AppError::BadRequest("STARRED is not a folder, import into a real folder instead".to_string()));
+        }
+        use base64::{Engine as _, engine::general_purpose};
+
+        // Canonical ids map to well-known folder names, anything else is a folder id
+        let canonical = label_id.to_uppercase();
+        let folder = if SYSTEM_LABELS.contains(&canonical.as_str()) { folder_ref(&canonical) } else { label_id };
+        let url = format!("https://graph.microsoft.com/v1.0/me/mailFolders/{}/messages", folder);
+
+        // Graph takes MIME content as a base64 text/plain body. Exchange keeps such items
+        // flagged as drafts, there is no way to clear that for MIME-created messages.
+        let res = self.client.post(&url)
+            .bearer_auth(token)
+            .header("Content-Type", "text/plain")
+            .body(general_purpose::STANDARD.encode(&raw))
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+             return Err(AppError::OutlookApi(res.error_for_status().unwrap_err()));
+        }
+
+        let data: serde_json::Value = res.json().await?;
+        Ok(json!({
+            "id": data["id"],
+            "thread_id": data["conversationId"],
+        }))
     }
 
     async fn get_profile(&self, token: &str) -> Result<crate::handlers::provider::UserProfile, AppError> {
@@ -322,3 +1073,299 @@ impl EmailProvider for OutlookProvider {
         })
     }
 }
+
+// Canonical system id -> Graph well-known folder name
+const WELL_KNOWN_FOLDERS: [(&str, &str); 6] = [
+    ("INBOX", "inbox"),
+    ("SENT", "sentitems"),
+    ("DRAFT", "drafts"),
+    ("TRASH", "deleteditems"),
+    ("SPAM", "junkemail"),
+    ("ARCHIVE", "archive"),
+];
+
+const FOLDER_QUERY: &str = "$select=id,displayName,parentFolderId,childFolderCount,unreadItemCount,totalItemCount&$top=100";
+
+// Graph accepts well-known names wherever it takes a folder id
+fn folder_ref(label_id: &str) -> &str {
+    WELL_KNOWN_FOLDERS.iter()
+        .find(|(canonical, _)| *canonical == label_id)
+        .map(|(_, name)| *name)
+        .unwrap_or(label_id)
+}
+
+fn parse_folder(f: &serde_json::Value) -> Label {
+    Label {
+        id: f["id"].as_str().unwrap_or("").to_string(),
+        name: f["displayName"].as_str().unwrap_or("").to_string(),
+        label_type: Some("user".to_string()),
+        parent_id: f["parentFolderId"].as_str().map(|s| s.to_string()),
+        unread_count: f["unreadItemCount"].as_u64(),
+        total_count: f["totalItemCount"].as_u64(),
+        ..Default::default()
+    }
+}
+
+// Messages collection for the requested label (folder) or the whole mailbox
+fn collection_url(folder: Option<&str>) -> String {
+    match folder {
+        // Flagged mail is spread across folders, first_page_url filters for it
+        Some("STARRED") | None => "https://graph.microsoft.com/v1.0/me/messages".to_string(),
+        Some(label_id) => format!("https://graph.microsoft.com/v1.0/me/mailFolders/{}/messages", folder_ref(label_id)),
+    }
+}
+
+// PR_MESSAGE_SIZE, which Graph only exposes as a MAPI extended property
+const MESSAGE_SIZE_PROPERTY: &str = "Integer 0x0E08";
+
+// URL of the first page of a message listing
+fn first_page_url(params: &ListParams, folder: Option<&str>, graph: &GraphQuery) -> String {
+    let url = collection_url(folder);
+
+    let mut query = Vec::new();
+    query.push("$select=id,subject,from,toRecipients,ccRecipients,receivedDateTime,isRead,hasAttachments,bodyPreview,conversationId,parentFolderId,importance,flag,categories".to_string());
+    // Graph has no size property on messages, PidTagMessageSize comes as an extended property
+    query.push(format!("$expand=singleValueExtendedProperties($filter=id eq '{}')", MESSAGE_SIZE_PROPERTY));
+    
+    // Fixed Point 12: Avoid duplicate $top
+    let top = params.max_results.unwrap_or(10);
+    query.push(format!("$top={}", top));
+
+    if let Some(search) = &graph.search {
+         query.push(format!("$search=\"{}\"", urlencoding::encode(search)));
+    } else {
+         // $count and $orderby cannot be combined with $search on messages
+         query.push("$count=true".to_string());
+
+         let mut filter = listing_filter(folder, graph);
+         if !filter.is_empty() {
+             // Graph wants the $orderby property to lead the $filter
+             if !filter[0].starts_with("receivedDateTime") {
+                 filter.insert(0, "receivedDateTime ge 1900-01-01T00:00:00Z".to_string());
+             }
+             query.push(format!("$filter={}", urlencoding::encode(&filter.join(" and "))));
+         }
+         query.push("$orderby=receivedDateTime desc".to_string());
+    }
+
+    format!("{}?{}", url, query.join("&"))
+}
+
+// $filter clauses of a listing without $search: the query's own plus the flag for STARRED
+fn listing_filter(folder: Option<&str>, graph: &GraphQuery) -> Vec<String> {
+    let mut filter = graph.filter.clone();
+    if folder == Some("STARRED") {
+        filter.push("flag/flagStatus eq 'flagged'".to_string());
+    }
+    filter
+}
+
+// Only ever follow Graph URLs with the user's Bearer token
+fn checked_next_link(link: String) -> Result<String, AppError> {
+    if link.starts_with("https://graph.microsoft.com/") {
+        Ok(link)
+    } else {
+        Err(AppError::BadRequest("Invalid page token".to_string()))
+    }
+}
+
+// Convert a Graph message from a listing into a MessageSummary
+fn parse_summary(m: &serde_json::Value) -> MessageSummary {
+    let unread = !m["isRead"].as_bool().unwrap_or(true);
+    let flagged = m["flag"]["flagStatus"].as_str() == Some("flagged");
+
+    // Same shape as Gmail's labelIds: the folder plus UNREAD / STARRED
+    let mut label_ids: Vec<String> = m["parentFolderId"].as_str().map(|s| s.to_string()).into_iter().collect();
+    if unread {
+        label_ids.push("UNREAD".to_string());
+    }
+    if flagged {
+        label_ids.push("STARRED".to_string());
+    }
+
+    MessageSummary {
+        id: m["id"].as_str().unwrap_or("").to_string(),
+        thread_id: m["conversationId"].as_str().unwrap_or("").to_string(),
+        snippet: m["bodyPreview"].as_str().unwrap_or("").to_string(),
+        subject: m["subject"].as_str().map(|s| s.to_string()),
+        from: graph_address(&m["from"]),
+        date: m["receivedDateTime"].as_str().and_then(normalize_date),
+        unread,
+        has_attachments: m["hasAttachments"].as_bool().unwrap_or(false),
+        messages_in_thread: None,
+        participants: None,
+        to: m["toRecipients"].as_array().map(|r| recipient_addresses(r)),
+        cc: m["ccRecipients"].as_array().map(|r| recipient_addresses(r)),
+        label_ids,
+        size_estimate: m["singleValueExtendedProperties"].as_array()
+            .and_then(|props| props.iter().find(|p| p["id"].as_str().is_some_and(|id| id.eq_ignore_ascii_case(MESSAGE_SIZE_PROPERTY))))
+            .and_then(|p| p["value"].as_str())
+            .and_then(|v| v.parse().ok()),
+        importance: m["importance"].as_str().map(|s| s.to_lowercase()),
+        flagged,
+        categories: m["categories"].as_array()
+            .map(|c| c.iter().filter_map(|c| c.as_str().map(|s| s.to_string())).collect())
+            .unwrap_or_default(),
+        raw_date: m["receivedDateTime"].as_str().map(|s| s.to_string()),
+        raw_from: None,
+    }
+}
+
+// A Graph recipient ({"emailAddress": {"name", "address"}})
+fn graph_address(recipient: &serde_json::Value) -> Option<EmailAddress> {
+    let email = &recipient["emailAddress"];
+    let address = email["address"].as_str();
+    let name = email["name"].as_str();
+    (address.is_some() || name.is_some()).then(|| EmailAddress::from_parts(name, address))
+}
+
+fn recipient_addresses(recipients: &[serde_json::Value]) -> Vec<EmailAddress> {
+    recipients.iter().filter_map(graph_address).collect()
+}
+
+// Convert a Graph message resource into a CleanMessage
+fn parse_message(data: &serde_json::Value) -> CleanMessage {
+    let subject = data["subject"].as_str().map(|s| s.to_string());
+    let from = graph_address(&data["from"]);
+
+    let raw_date = data["receivedDateTime"].as_str().map(|s| s.to_string());
+    let date = raw_date.as_deref().and_then(normalize_date);
+    let snippet = data["bodyPreview"].as_str().unwrap_or("").to_string();
+    
+    // Extract recipients
+    let to = data["toRecipients"].as_array().map(|r| recipient_addresses(r));
+    let cc = data["ccRecipients"].as_array().map(|r| recipient_addresses(r));
+
+    CleanMessage {
+        id: data["id"].as_str().unwrap_or("").to_string(),
+        thread_id: data["conversationId"].as_str().map(|s| s.to_string()),
+        subject,
+        from,
+        to,
+        cc,
+        date,
+        snippet,
+        body_text: body_of_type(data, "text"),
+        body_html: body_of_type(data, "html"),
+        attachments: vec![],
+        reply_text: None,
+        raw_date,
+        raw_from: None,
+    }
+}
+
+// get_message / get_thread shape: the message fetched as HTML, its text body and attachments
+pub fn parse_full_message(data: &serde_json::Value, text: &serde_json::Value, attachments: Vec<AttachmentSummary>) -> CleanMessage {
+    let mut message = parse_message(data);
+    message.body_text = text["body"]["content"].as_str().map(|s| s.to_string());
+    message.attachments = attachments;
+    message
+}
+
+// An entry of the attachments collection; contentId is read separately for inline ones
+pub fn parse_attachment(a: &serde_json::Value, content_id: Option<String>) -> AttachmentSummary {
+    AttachmentSummary {
+        filename: a["name"].as_str().unwrap_or("unnamed").to_string(),
+        content_type: a["contentType"].as_str().unwrap_or("application/octet-stream").to_string(),
+        size: a["size"].as_u64().unwrap_or(0) as usize,
+        id: a["id"].as_str().map(|s| s.to_string()),
+        content_id,
+    }
+}
+
+// The body content, if Graph returned it in the given format ("text" or "html")
+fn body_of_type(data: &serde_json::Value, content_type: &str) -> Option<String> {
+    data["body"]["contentType"].as_str()
+        .filter(|t| t.eq_ignore_ascii_case(content_type))
+        .and_then(|_| data["body"]["content"].as_str())
+        .map(|s| s.to_string())
+}
+
+// Build a Graph message resource, shared by sendMail and the drafts endpoints
+fn build_message_json(req: &SendMessageRequest, sender: Option<&EmailAddress>) -> serde_json::Value {
+    let recipients = recipients_json(&req.to);
+    let cc_recipients = recipients_json(req.cc.as_deref().unwrap_or_default());
+    let attachments_json = attachments_json(req.attachments.as_deref().unwrap_or_default());
+
+    let mut message = json!({
+        "subject": req.subject,
+        "body": {
+            "contentType": "HTML",
+            "content": req.body
+        },
+        "toRecipients": recipients,
+        "ccRecipients": cc_recipients,
+        "attachments": attachments_json
+    });
+
+    if let Some(bcc) = &req.bcc {
+        message["bccRecipients"] = json!(recipients_json(bcc));
+    }
+    if let Some(reply_to) = &req.reply_to {
+        message["replyTo"] = json!(recipients_json(reply_to));
+    }
+    if let Some(sender) = sender {
+        message["from"] = recipient_json(sender);
+    }
+    if let Some(importance) = req.importance {
+        message["importance"] = json!(importance.as_str());
+    }
+    if req.request_read_receipt {
+        message["isReadReceiptRequested"] = json!(true);
+    }
+    if let Some(headers) = req.headers.as_ref().filter(|h| !h.is_empty()) {
+        message["internetMessageHeaders"] = json!(headers.iter()
+            .map(|(name, value)| json!({ "name": name, "value": value }))
+            .collect::<Vec<_>>());
+    }
+
+    message
+}
+
+fn recipients_json(addresses: &[String]) -> Vec<serde_json::Value> {
+    addresses.iter().map(|a| recipient_json(&EmailAddress::parse(a))).collect()
+}
+
+fn recipient_json(address: &EmailAddress) -> serde_json::Value {
+    let mut email = json!({ "address": address.address });
+    if let Some(name) = &address.name {
+        email["name"] = json!(name);
+    }
+    json!({ "emailAddress": email })
+}
+
+fn attachments_json(attachments: &[super::provider::Attachment]) -> Vec<serde_json::Value> {
+    use base64::{Engine as _, engine::general_purpose};
+    attachments.iter().map(|att| {
+        let mut attachment = json!({
+            "@odata.type": "#microsoft.graph.fileAttachment",
+            "name": att.filename,
+            "contentType": att.mime_type,
+            "contentBytes": general_purpose::STANDARD.encode(&att.content) 
+        });
+        if let Some(content_id) = &att.content_id {
+            attachment["isInline"] = json!(true);
+            attachment["contentId"] = json!(content_id);
+        }
+        attachment
+    }).collect()
+}
+
+// Convert a Graph message in the Drafts folder into a Draft (the message id is the draft id)
+fn parse_draft(data: &serde_json::Value) -> Draft {
+    let id = data["id"].as_str().unwrap_or("").to_string();
+
+    Draft {
+        message_id: Some(id.clone()),
+        id,
+        thread_id: data["conversationId"].as_str().map(|s| s.to_string()),
+        subject: data["subject"].as_str().map(|s| s.to_string()),
+        to: data["toRecipients"].as_array().map(|recipients| {
+            recipients.iter()
+                .filter_map(|r| r["emailAddress"]["address"].as_str().map(|s| s.to_string()))
+                .collect()
+        }),
+        snippet: data["bodyPreview"].as_str().map(|s| s.to_string()),
+        date: data["lastModifiedDateTime"].as_str().and_then(normalize_date),
+    }
+}
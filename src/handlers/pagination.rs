@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::future::Future;
+use std::sync::OnceLock;
+
+use super::provider::{ListParams, MessageList};
+use crate::error::AppError;
+use crate::services::signing::Signer;
+
+// Providers only hand out opaque "next page" tokens. We wrap them in our own cursor that also
+// records the page number and the query it belongs to, sealed with the server key so any
+// instance can resume any page (no per-process cache) and clients cannot forge upstream tokens.
+static CURSOR_SIGNER: OnceLock<Signer> = OnceLock::new();
+
+/// How many pages we are willing to walk forward to honour a `page_number` jump
+const MAX_PAGE_SKIP: u32 = 10;
+
+/// Install the server key used to seal cursors. Called once at startup.
+pub fn init(secret: &str) {
+    let _ = CURSOR_SIGNER.set(Signer::new(secret));
+}
+
+fn signer() -> Result<&'static Signer, AppError> {
+    CURSOR_SIGNER
+        .get()
+        .ok_or_else(|| AppError::Config("Pagination cursor key not initialized".to_string()))
+}
+
+#[derive(Serialize, Deserialize)]
+struct Cursor {
+    #[serde(rename = "f")]
+    fingerprint: String,
+    #[serde(rename = "n")]
+    page: u32,
+    #[serde(rename = "t")]
+    token: String,
+}
+
+/// The page to serve and the upstream token to fetch it with (`None` for the first page)
+pub struct ResolvedPage {
+    pub page: u32,
+    pub upstream_token: Option<String>,
+}
+
+// Ties a cursor to the provider and query it was issued for
+fn fingerprint(provider: &str, params: &ListParams) -> String {
+    let digest = Sha256::digest(format!(
+        "{}|{}|{}|{}|{}",
+        provider,
+        params.q.as_deref().unwrap_or(""),
+        params.label_ids.as_deref().unwrap_or(""),
+        params.max_results.unwrap_or(10),
+        // Thread and message listings are paged independently upstream
+        params.collapse_threads.unwrap_or(false)
+    ));
+    digest.iter().take(8).map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Wrap the upstream token of page `page` into a cursor
+pub fn encode(provider: &str, params: &ListParams, page: u32, upstream_token: &str) -> Result<String, AppError> {
+    let cursor = Cursor {
+        fingerprint: fingerprint(provider, params),
+        page,
+        token: upstream_token.to_string(),
+    };
+    Ok(signer()?.seal(&serde_json::to_vec(&cursor)?))
+}
+
+fn decode(provider: &str, params: &ListParams, value: &str) -> Result<Cursor, AppError> {
+    let cursor: Cursor = signer()?
+        .open(value)
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .ok_or_else(|| AppError::BadRequest("Invalid page token".to_string()))?;
+
+    if cursor.fingerprint != fingerprint(provider, params) {
+        return Err(AppError::BadRequest("Page token does not match the current query".to_string()));
+    }
+    Ok(cursor)
+}
+
+/// Resolve the requested page from `page_token` (our cursor) and/or `page_number`.
+/// Jumps ahead of the cursor are served by walking the upstream tokens with `fetch_next`,
+/// which returns the upstream token following the given one (`None` = first page).
+/// Returns `None` if the page cannot be reached.
+pub async fn resolve<F, Fut>(provider: &str, params: &ListParams, fetch_next: F) -> Result<Option<ResolvedPage>, AppError>
+where
+    F: Fn(Option<String>) -> Fut,
+    Fut: Future<Output = Result<Option<String>, AppError>>,
+{
+    let (mut page, mut upstream_token) = match params.page_token.as_deref().filter(|t| !t.is_empty()) {
+        Some(value) => {
+            let cursor = decode(provider, params, value)?;
+            (cursor.page, Some(cursor.token))
+        }
+        None => (1, None),
+    };
+
+    let target = params.page_number.unwrap_or(page).max(1);
+    if target < page {
+        // Tokens only go forward, restart from the first page
+        page = 1;
+        upstream_token = None;
+    }
+    if target - page > MAX_PAGE_SKIP {
+        return Ok(None);
+    }
+
+    while page < target {
+        match fetch_next(upstream_token.clone()).await? {
+            Some(next) => {
+                upstream_token = Some(next);
+                page += 1;
+            }
+            None => return Ok(None),
+        }
+    }
+
+    Ok(Some(ResolvedPage { page, upstream_token }))
+}
+
+/// Empty listing for a page number we cannot reach
+pub fn not_found(params: &ListParams) -> MessageList {
+    MessageList {
+        page: params.page_number.unwrap_or(1),
+        warnings: vec!["Page not reachable from the given page token. Please navigate sequentially.".to_string()],
+        ..Default::default()
+    }
+}
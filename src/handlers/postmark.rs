@@ -1,4 +1,4 @@
-use super::provider::{EmailProvider, ListParams, SendMessageRequest, BatchModifyRequest, CleanMessage, UserProfile, Label};
+use super::provider::{EmailProvider, EmailAddress, ListParams, MessageList, MessageFormat, SendMessageRequest, BatchModifyRequest, CleanMessage, UserProfile, Label, Thread, AttachmentContent, Draft, ReplyMode, ReplyRequest, BatchItemResult, LabelRequest};
 use crate::error::AppError;
 use async_trait::async_trait;
 use reqwest::Client;
@@ -24,19 +24,23 @@ impl PostmarkProvider {
 
 #[async_trait]
 impl EmailProvider for PostmarkProvider {
-    async fn list_messages(&self, _token: &str, _params: ListParams) -> Result<serde_json::Value, AppError> {
+    async fn list_messages(&self, _token: &str, _params: ListParams) -> Result<MessageList, AppError> {
         // Postmark in this context is send-only. Return empty list.
-        Ok(json!({
-            "messages": [],
-            "nextPageToken": null,
-            "resultSizeEstimate": 0
-        }))
+        Ok(MessageList::new(Vec::new(), 1, None, Some(0)))
     }
 
-    async fn get_message(&self, _token: &str, _id: &str) -> Result<CleanMessage, AppError> {
+    async fn get_message(&self, _token: &str, _id: &str, _format: MessageFormat) -> Result<CleanMessage, AppError> {
         Err(AppError::BadRequest("Message viewing not supported for Postmark".to_string()))
     }
 
+    async fn get_thread(&self, _token: &str, _thread_id: &str) -> Result<Thread, AppError> {
+        Err(AppError::BadRequest("Thread viewing not supported for Postmark".to_string()))
+    }
+
+    async fn get_attachment(&self, _token: &str, _message_id: &str, _attachment_id: &str) -> Result<AttachmentContent, AppError> {
+        Err(AppError::BadRequest("Attachment download not supported for Postmark".to_string()))
+    }
+
     async fn send_message(&self, _token: &str, req: SendMessageRequest) -> Result<serde_json::Value, AppError> {
         let url = "https://api.postmarkapp.com/email";
         
@@ -48,11 +52,16 @@ impl EmailProvider for PostmarkProvider {
             use base64::{Engine as _, engine::general_purpose::STANDARD};
             let content_base64 = STANDARD.encode(&att.content);
             
-            json!({
+            let mut attachment = json!({
                 "Name": att.filename,
                 "Content": content_base64,
                 "ContentType": att.mime_type,
-            })
+            });
+            // Postmark wants the reference exactly as the HTML uses it
+            if let Some(content_id) = &att.content_id {
+                attachment["ContentID"] = json!(format!("cid:{}", content_id));
+            }
+            attachment
         }).collect();
 
         // Join recipients
@@ -61,8 +70,9 @@ impl EmailProvider for PostmarkProvider {
 
         // Construct body
         // Note: 'body' in SendMessageRequest is expected to be HTML for our app
+        let from = EmailAddress::from_parts(req.from_name.as_deref(), Some(&from_address)).to_string();
         let mut body_json = json!({
-            "From": from_address,
+            "From": from,
             "To": to,
             "Subject": req.subject,
             "HtmlBody": req.body,
@@ -72,6 +82,27 @@ impl EmailProvider for PostmarkProvider {
         if let Some(cc_val) = cc {
             body_json["Cc"] = json!(cc_val);
         }
+        if let Some(bcc) = &req.bcc {
+            body_json["Bcc"] = json!(bcc.join(","));
+        }
+        if let Some(reply_to) = &req.reply_to {
+            body_json["ReplyTo"] = json!(reply_to.join(","));
+        }
+
+        // Postmark has no fields for these, they travel as plain headers
+        let mut headers: Vec<serde_json::Value> = Vec::new();
+        for (name, value) in req.importance.iter().flat_map(|i| i.mime_headers()) {
+            headers.push(json!({ "Name": name, "Value": value }));
+        }
+        if req.request_read_receipt {
+            headers.push(json!({ "Name": "Disposition-Notification-To", "Value": from_address }));
+        }
+        for (name, value) in req.headers.iter().flatten() {
+            headers.push(json!({ "Name": name, "Value": value }));
+        }
+        if !headers.is_empty() {
+            body_json["Headers"] = json!(headers);
+        }
 
         let res = self.client.post(url)
             .header("X-Postmark-Server-Token", if _token.is_empty() { &self.server_token } else { _token })
@@ -91,14 +122,80 @@ impl EmailProvider for PostmarkProvider {
         Ok(data)
     }
 
-    async fn list_labels(&self, _token: &str) -> Result<Vec<Label>, AppError> {
+    async fn reply(&self, _token: &str, _message_id: &str, _mode: ReplyMode, _req: ReplyRequest) -> Result<serde_json::Value, AppError> {
+        Err(AppError::BadRequest("Replies are not supported for Postmark".to_string()))
+    }
+
+    async fn list_drafts(&self, _token: &str, _max_results: Option<u32>) -> Result<Vec<Draft>, AppError> {
+        Err(AppError::BadRequest("Drafts are not supported for Postmark".to_string()))
+    }
+
+    async fn create_draft(&self, _token: &str, _req: SendMessageRequest) -> Result<Draft, AppError> {
+        Err(AppError::BadRequest("Drafts are not supported for Postmark".to_string()))
+    }
+
+    async fn update_draft(&self, _token: &str, _draft_id: &str, _req: SendMessageRequest) -> Result<Draft, AppError> {
+        Err(AppError::BadRequest("Drafts are not supported for Postmark".to_string()))
+    }
+
+    async fn send_draft(&self, _token: &str, _draft_id: &str) -> Result<serde_json::Value, AppError> {
+        Err(AppError::BadRequest("Drafts are not supported for Postmark".to_string()))
+    }
+
+    async fn mark_read(&self, _token: &str, _id: &str, _read: bool) -> Result<(), AppError> {
+        Err(AppError::BadRequest("Message state changes are not supported for Postmark".to_string()))
+    }
+
+    async fn set_starred(&self, _token: &str, _id: &str, _starred: bool) -> Result<(), AppError> {
+        Err(AppError::BadRequest("Message state changes are not supported for Postmark".to_string()))
+    }
+
+    async fn archive(&self, _token: &str, _id: &str) -> Result<(), AppError> {
+        Err(AppError::BadRequest("Message state changes are not supported for Postmark".to_string()))
+    }
+
+    async fn trash(&self, _token: &str, _id: &str) -> Result<(), AppError> {
+        Err(AppError::BadRequest("Message state changes are not supported for Postmark".to_string()))
+    }
+
+    async fn restore(&self, _token: &str, _id: &str) -> Result<(), AppError> {
+        Err(AppError::BadRequest("Message state changes are not supported for Postmark".to_string()))
+    }
+
+    async fn delete_message(&self, _token: &str, _id: &str) -> Result<(), AppError> {
+        Err(AppError::BadRequest("Message state changes are not supported for Postmark".to_string()))
+    }
+
+    async fn get_raw_message(&self, _token: &str, _id: &str) -> Result<Vec<u8>, AppError> {
+        Err(AppError::BadRequest("Raw export not supported for Postmark".to_string()))
+    }
+
+    async fn import_message(&self, _token: &str, _raw: Vec<u8>, _label_id: &str) -> Result<serde_json::Value, AppError> {
+        Err(AppError::BadRequest("Import not supported for Postmark".to_string()))
+    }
+
+    async fn list_labels(&self, _token: &str, _with_counts: bool) -> Result<Vec<Label>, AppError> {
         // No labels for Postmark
         Ok(vec![])
     }
 
-    async fn batch_modify_labels(&self, _token: &str, _req: BatchModifyRequest) -> Result<(), AppError> {
-        // Not supported
-        Ok(())
+    async fn create_label(&self, _token: &str, _req: LabelRequest) -> Result<Label, AppError> {
+        Err(AppError::BadRequest("Labels are not supported for Postmark".to_string()))
+    }
+
+    async fn update_label(&self, _token: &str, _label_id: &str, _req: LabelRequest) -> Result<Label, AppError> {
+        Err(AppError::BadRequest("Labels are not supported for Postmark".to_string()))
+    }
+
+    async fn delete_label(&self, _token: &str, _label_id: &str) -> Result<(), AppError> {
+        Err(AppError::BadRequest("Labels are not supported for Postmark".to_string()))
+    }
+
+    async fn batch_modify_labels(&self, _token: &str, req: BatchModifyRequest) -> Result<Vec<BatchItemResult>, AppError> {
+        // Not supported, report every id as skipped rather than pretending it worked
+        Ok(req.ids.into_iter()
+            .map(|id| BatchItemResult::skipped(id, "Labels are not supported for Postmark"))
+            .collect())
     }
 
     async fn get_profile(&self, _token: &str) -> Result<UserProfile, AppError> {
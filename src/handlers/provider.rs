@@ -1,6 +1,15 @@
 use async_trait::async_trait;
 use crate::error::AppError;
+use super::address::normalize_recipients;
+use super::mime::{check_header_value, sanitize_header_text};
 use serde::{Deserialize, Serialize};
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+pub use super::address::EmailAddress;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct MessageSummary {
@@ -8,25 +17,95 @@ pub struct MessageSummary {
     pub thread_id: String,
     pub snippet: String,
     pub subject: Option<String>,
-    pub from: Option<String>,
-    pub date: Option<String>,
+    pub from: Option<EmailAddress>,
+    pub date: Option<String>, // RFC 3339 UTC
     pub unread: bool,
     pub has_attachments: bool,
     pub messages_in_thread: Option<u32>,
+    pub participants: Option<Vec<EmailAddress>>, // Senders of the thread, only set for collapsed threads
+    pub to: Option<Vec<EmailAddress>>,
+    pub cc: Option<Vec<EmailAddress>>,
+    #[serde(default)]
+    pub label_ids: Vec<String>, // Canonical ids where one exists (INBOX, STARRED, ...), plus UNREAD
+    pub size_estimate: Option<u64>, // Bytes
+    pub importance: Option<String>, // "low", "normal" or "high"
+    #[serde(default)]
+    pub flagged: bool, // Gmail star / Outlook flag
+    #[serde(default)]
+    pub categories: Vec<String>, // Gmail inbox tabs (social, promotions, ...) / Outlook categories
+    // Unparsed provider values, for debugging
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_date: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_from: Option<String>,
+}
+
+/// One page of a message listing, the same schema for every provider
+#[derive(Serialize, Debug, Default)]
+pub struct MessageList {
+    pub messages: Vec<MessageSummary>,
+    /// Sealed cursor for the following page, `None` on the last page
+    #[serde(rename = "nextPageToken")]
+    pub next_page_token: Option<String>,
+    pub page: u32,
+    pub next_page: Option<u32>,
+    #[serde(rename = "resultSizeEstimate")]
+    pub result_size_estimate: Option<u64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+impl MessageList {
+    pub fn new(messages: Vec<MessageSummary>, page: u32, next_page_token: Option<String>, result_size_estimate: Option<u64>) -> Self {
+        Self {
+            messages,
+            next_page: next_page_token.as_ref().map(|_| page + 1),
+            next_page_token,
+            page,
+            result_size_estimate,
+            warnings: Vec::new(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CleanMessage {
     pub id: String,
+    pub thread_id: Option<String>,
     pub subject: Option<String>,
-    pub from: Option<String>,
-    pub to: Option<Vec<String>>,
-    pub cc: Option<Vec<String>>,
-    pub date: Option<String>,
+    pub from: Option<EmailAddress>,
+    pub to: Option<Vec<EmailAddress>>,
+    pub cc: Option<Vec<EmailAddress>>,
+    pub date: Option<String>, // RFC 3339 UTC
     pub snippet: String,
     pub body_text: Option<String>,
     pub body_html: Option<String>,
     pub attachments: Vec<AttachmentSummary>,
+    // Only set when returned as part of a thread: body_text without the quoted history
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_text: Option<String>,
+    // Unparsed provider values, for debugging
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_date: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_from: Option<String>,
+}
+
+/// How a provider reads a message for get_message
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageFormat {
+    /// Structured fetch: text/html bodies and attachment metadata, attachment bytes stay upstream
+    #[default]
+    Full,
+    /// Download and parse the whole MIME source, attachments included (Gmail only)
+    Raw,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Thread {
+    pub id: String,
+    pub messages: Vec<CleanMessage>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -34,7 +113,16 @@ pub struct AttachmentSummary {
     pub filename: String,
     pub content_type: String,
     pub size: usize,
-    pub id: Option<String>,
+    pub id: Option<String>, // Provider attachment id, usable with get_attachment
+    pub content_id: Option<String>, // MIME Content-ID (for inline images)
+}
+
+/// Attachment bytes as returned by the provider, ready to be streamed to the client
+pub struct AttachmentContent {
+    pub filename: String,
+    pub mime_type: String,
+    pub size: Option<u64>,
+    pub body: axum::body::Body,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -42,6 +130,8 @@ pub struct Attachment {
     pub filename: String,
     pub content: Vec<u8>,
     pub mime_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_id: Option<String>, // Set for inline images, referenced from the HTML as cid:<content_id>
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -51,7 +141,7 @@ pub struct UserProfile {
     pub picture: Option<String>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Default)]
 pub struct SendMessageRequest {
     pub to: Vec<String>,
     pub cc: Option<Vec<String>>,
@@ -59,13 +149,435 @@ pub struct SendMessageRequest {
     pub body: String,
     pub thread_id: Option<String>,
     pub attachments: Option<Vec<Attachment>>,
+    pub in_reply_to: Option<String>, // Message-ID of the message being answered
+    pub references: Option<String>,
+    pub bcc: Option<Vec<String>>,
+    pub reply_to: Option<Vec<String>>,
+    pub from_name: Option<String>, // Display name for the sender, the address is always the account's
+    pub importance: Option<Importance>,
+    #[serde(default)]
+    pub request_read_receipt: bool,
+    pub headers: Option<BTreeMap<String, String>>, // Custom headers, names must start with "X-"
+    #[serde(default)]
+    pub inline_images: bool, // Download <img src="http..."> and send the images inline (cid:)
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Importance {
+    Low,
+    Normal,
+    High,
+}
+
+impl Importance {
+    /// Graph `importance` value
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Importance::Low => "low",
+            Importance::Normal => "normal",
+            Importance::High => "high",
+        }
+    }
+
+    /// `Importance` and `X-Priority` headers, the two forms mail clients look at
+    pub fn mime_headers(&self) -> [(&'static str, &'static str); 2] {
+        match self {
+            Importance::Low => [("Importance", "Low"), ("X-Priority", "5 (Lowest)")],
+            Importance::Normal => [("Importance", "Normal"), ("X-Priority", "3 (Normal)")],
+            Importance::High => [("Importance", "High"), ("X-Priority", "1 (Highest)")],
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ReplyRequest {
+    pub body: String, // HTML, the quoted original is appended by the provider
+    pub to: Option<Vec<String>>, // Required for forward, overrides the computed recipients of a reply
+    pub cc: Option<Vec<String>>,
+    pub attachments: Option<Vec<Attachment>>,
+}
+
+impl SendMessageRequest {
+    /// Checks every value that ends up in a header line of the outgoing message, so a CR/LF
+    /// coming from a client (or a Bubble field) cannot inject headers such as Bcc.
+    /// Recipients come back validated, deduplicated and in `"Name" <address>` form.
+    pub fn sanitize_headers(mut self) -> Result<Self, AppError> {
+        self.subject = sanitize_header_text("subject", &self.subject)?;
+        let recipients = normalize_recipients(
+            &self.to,
+            self.cc.as_deref().unwrap_or_default(),
+            self.bcc.as_deref().unwrap_or_default(),
+            self.reply_to.as_deref().unwrap_or_default(),
+        )?;
+        self.to = format_addresses(&recipients.to);
+        self.cc = self.cc.map(|_| format_addresses(&recipients.cc));
+        self.bcc = self.bcc.map(|_| format_addresses(&recipients.bcc));
+        self.reply_to = self.reply_to.map(|_| format_addresses(&recipients.reply_to));
+        if let Some(from_name) = &self.from_name {
+            self.from_name = Some(sanitize_header_text("from_name", from_name)?).filter(|n| !n.is_empty());
+        }
+        if let Some(headers) = self.headers.take() {
+            let mut sanitized = BTreeMap::new();
+            for (name, value) in headers {
+                check_custom_header_name(&name)?;
+                // `importance` already writes X-Priority
+                if let Some(importance) = self.importance {
+                    if importance.mime_headers().iter().any(|(header, _)| header.eq_ignore_ascii_case(&name)) {
+                        return Err(AppError::BadRequest(format!("Custom header '{}' conflicts with importance", name)));
+                    }
+                }
+                let value = sanitize_header_text(&format!("header {}", name), &value)?;
+                sanitized.insert(name, value);
+            }
+            self.headers = Some(sanitized);
+        }
+        if let Some(in_reply_to) = &self.in_reply_to {
+            check_header_value("in_reply_to", in_reply_to)?;
+        }
+        if let Some(references) = &self.references {
+            check_header_value("references", references)?;
+        }
+        self.attachments = sanitize_attachments(self.attachments)?;
+        Ok(self)
+    }
+
+    /// Sending needs a recipient, drafts may be saved without one. Call after `sanitize_headers`,
+    /// which drops empty entries.
+    pub fn require_recipients(self) -> Result<Self, AppError> {
+        let has_recipient = !self.to.is_empty()
+            || self.cc.as_ref().is_some_and(|cc| !cc.is_empty())
+            || self.bcc.as_ref().is_some_and(|bcc| !bcc.is_empty());
+        if !has_recipient {
+            return Err(AppError::BadRequest("At least one recipient (to, cc or bcc) is required".to_string()));
+        }
+        Ok(self)
+    }
+}
+
+/// Cap on images pulled from one message body
+const MAX_INLINE_IMAGES: usize = 20;
+const MAX_INLINE_IMAGE_SIZE: usize = 5 * 1024 * 1024;
+/// Budget for one image, redirects and body included
+const INLINE_IMAGE_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_INLINE_IMAGE_REDIRECTS: usize = 5;
+
+impl SendMessageRequest {
+    /// With `inline_images` set, downloads the http(s) images the HTML body references, attaches
+    /// them with a Content-ID and rewrites their `src` to `cid:`. Clients block remote images by
+    /// default but show inline ones. An image that cannot be downloaded keeps its URL.
+    /// Only public hosts are contacted; call after `sanitize_headers` so a rejected request
+    /// never triggers downloads.
+    pub async fn inline_remote_images(mut self) -> Result<Self, AppError> {
+        use futures::stream::{self, StreamExt};
+        const CONCURRENCY_LIMIT: usize = 5;
+
+        if !self.inline_images {
+            return Ok(self);
+        }
+
+        static IMG_SRC: OnceLock<Regex> = OnceLock::new();
+        let img_src = IMG_SRC.get_or_init(|| {
+            Regex::new(r#"(?i)(<img\b[^>]*?\bsrc\s*=\s*["'])(https?://[^"']+)(["'])"#).expect("invalid img src pattern")
+        });
+        let mut urls: Vec<String> = Vec::new();
+        for caps in img_src.captures_iter(&self.body) {
+            if urls.len() < MAX_INLINE_IMAGES && !urls.iter().any(|u| u == &caps[2]) {
+                urls.push(caps[2].to_string());
+            }
+        }
+
+        let downloads: Vec<(String, Option<Attachment>)> = stream::iter(urls)
+            .map(|url| async move {
+                let image = match tokio::time::timeout(INLINE_IMAGE_TIMEOUT, download_inline_image(&url)).await {
+                    Ok(image) => image,
+                    Err(_) => {
+                        tracing::warn!("Inline image {} timed out, keeping the remote URL", url);
+                        None
+                    }
+                };
+                (url, image)
+            })
+            .buffered(CONCURRENCY_LIMIT)
+            .collect()
+            .await;
+
+        let mut content_ids = HashMap::new();
+        let attachments = self.attachments.get_or_insert_with(Vec::new);
+        for (url, image) in downloads {
+            if let Some(image) = image {
+                content_ids.insert(url, image.content_id.clone().unwrap_or_default());
+                attachments.push(image);
+            }
+        }
+
+        self.body = img_src.replace_all(&self.body, |caps: &regex::Captures| match content_ids.get(&caps[2]) {
+            Some(content_id) => format!("{}cid:{}{}", &caps[1], content_id, &caps[3]),
+            None => caps[0].to_string(),
+        }).into_owned();
+
+        Ok(self)
+    }
+}
+
+// An <img src> URL as an inline attachment, None (logged) if it is not a reachable image
+async fn download_inline_image(src: &str) -> Option<Attachment> {
+    // src is an HTML attribute value, so &amp; and friends are decoded before fetching
+    let url = html_escape::decode_html_entities(src).into_owned();
+
+    let mut res = match fetch_public_url(&url).await {
+        Ok(res) if res.status().is_success() => res,
+        Ok(res) => {
+            tracing::warn!("Inline image {} returned {}, keeping the remote URL", url, res.status());
+            return None;
+        }
+        Err(e) => {
+            tracing::warn!("Inline image {} could not be downloaded, keeping the remote URL: {}", url, e);
+            return None;
+        }
+    };
+
+    let mime_type = res.headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .map(|v| v.trim().to_lowercase())
+        .unwrap_or_default();
+    if !mime_type.starts_with("image/") || check_header_value("image type", &mime_type).is_err() {
+        tracing::warn!("Inline image {} is {:?}, not an image, keeping the remote URL", url, mime_type);
+        return None;
+    }
+
+    // Read in chunks: Content-Length may be missing or wrong
+    let mut content = Vec::new();
+    loop {
+        match res.chunk().await {
+            Ok(Some(chunk)) if content.len() + chunk.len() <= MAX_INLINE_IMAGE_SIZE => content.extend_from_slice(&chunk),
+            Ok(Some(_)) => {
+                tracing::warn!("Inline image {} is larger than {} bytes, keeping the remote URL", url, MAX_INLINE_IMAGE_SIZE);
+                return None;
+            }
+            Ok(None) => break,
+            Err(e) => {
+                tracing::warn!("Inline image {} could not be read, keeping the remote URL: {}", url, e);
+                return None;
+            }
+        }
+    }
+
+    // Last path segment as the filename, query and fragment stripped
+    let filename = url.split(['?', '#']).next().unwrap_or_default()
+        .rsplit('/').next()
+        .and_then(|segment| urlencoding::decode(segment).ok())
+        .and_then(|segment| sanitize_header_text("image filename", &segment).ok())
+        .filter(|segment| !segment.is_empty())
+        .unwrap_or_else(|| "image".to_string());
+
+    Some(Attachment {
+        filename,
+        content,
+        mime_type,
+        content_id: Some(format!("{}@inline", uuid::Uuid::new_v4().simple())),
+    })
+}
+
+// GET on a URL chosen by the client. Redirects are followed by hand so that every hop's host is
+// resolved and checked, and each connection is pinned to the checked addresses (no DNS rebinding).
+async fn fetch_public_url(url: &str) -> Result<reqwest::Response, String> {
+    let mut url = reqwest::Url::parse(url).map_err(|e| e.to_string())?;
+
+    for _ in 0..=MAX_INLINE_IMAGE_REDIRECTS {
+        let client = public_host_client(&url).await?;
+        let res = client.get(url.clone()).send().await.map_err(|e| e.to_string())?;
+        if !res.status().is_redirection() {
+            return Ok(res);
+        }
+
+        let location = res.headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|v| v.to_str().ok())
+            .ok_or("redirect without a Location")?;
+        url = url.join(location).map_err(|e| e.to_string())?;
+    }
+
+    Err("too many redirects".to_string())
+}
+
+// A client that can only reach the URL's host, and only if every address it resolves to is public
+async fn public_host_client(url: &reqwest::Url) -> Result<reqwest::Client, String> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("unsupported scheme '{}'", url.scheme()));
+    }
+    let port = url.port_or_known_default().ok_or("missing port")?;
+
+    let builder = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .no_proxy();
+
+    let host = url.host_str().ok_or("missing host")?;
+    // IPv6 literals come bracketed
+    let builder = match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        Ok(ip) if is_public_ip(ip) => builder,
+        Ok(_) => return Err(format!("{} is not a public host", host)),
+        Err(_) => {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port)).await
+                .map_err(|e| format!("cannot resolve {}: {}", host, e))?
+                .collect();
+            if addrs.is_empty() || addrs.iter().any(|addr| !is_public_ip(addr.ip())) {
+                return Err(format!("{} does not resolve to a public address", host));
+            }
+            builder.resolve_to_addrs(host, &addrs)
+        }
+    };
+
+    builder.build().map_err(|e| e.to_string())
+}
+
+// False for loopback, private, link-local (cloud metadata), shared and reserved ranges
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || a == 0
+                || (a == 100 && (64..128).contains(&b)) // Carrier-grade NAT
+                || (a == 192 && b == 0 && c == 0) // IETF protocol assignments
+                || (a == 198 && (b == 18 || b == 19)) // Benchmarking
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            // IPv4-mapped and NAT64 addresses reach the embedded IPv4 host
+            if let Some(v4) = ip.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(v4));
+            }
+            if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+                let [a, b] = segments[6].to_be_bytes();
+                let [c, d] = segments[7].to_be_bytes();
+                return is_public_ip(IpAddr::V4(std::net::Ipv4Addr::new(a, b, c, d)));
+            }
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || (segments[0] & 0xfe00) == 0xfc00 // Unique local, includes AWS fd00:ec2::254
+                || (segments[0] & 0xffc0) == 0xfe80 // Link-local
+                || (segments[0] & 0xffc0) == 0xfec0 // Site-local
+                || segments[0] == 0x2001 && segments[1] == 0xdb8) // Documentation
+        }
+    }
+}
+
+impl ReplyRequest {
+    pub fn sanitize_headers(mut self) -> Result<Self, AppError> {
+        let recipients = normalize_recipients(self.to.as_deref().unwrap_or_default(), self.cc.as_deref().unwrap_or_default(), &[], &[])?;
+        self.to = self.to.map(|_| format_addresses(&recipients.to));
+        self.cc = self.cc.map(|_| format_addresses(&recipients.cc));
+        self.attachments = sanitize_attachments(self.attachments)?;
+        Ok(self)
+    }
+}
+
+// Only X- headers can be set, so standard ones (From, Bcc, Received, ...) cannot be overridden
+fn check_custom_header_name(name: &str) -> Result<(), AppError> {
+    let valid = name.len() > 2
+        && name.get(..2).is_some_and(|prefix| prefix.eq_ignore_ascii_case("x-"))
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    if !valid {
+        return Err(AppError::BadRequest(format!(
+            "Custom header '{}' must start with 'X-' and contain only letters, digits and '-'", name
+        )));
+    }
+    Ok(())
+}
+
+pub fn format_addresses(addresses: &[EmailAddress]) -> Vec<String> {
+    addresses.iter().map(ToString::to_string).collect()
+}
+
+fn sanitize_attachments(attachments: Option<Vec<Attachment>>) -> Result<Option<Vec<Attachment>>, AppError> {
+    attachments.map(|list| list.into_iter().map(|mut att| {
+        att.filename = sanitize_header_text("attachment filename", &att.filename)?;
+        check_header_value("attachment mime_type", &att.mime_type)?;
+        if let Some(content_id) = att.content_id.take() {
+            // Accept "<id>" and "cid:id" as well, the bare id is what goes into Content-ID
+            let content_id = content_id.trim().trim_start_matches("cid:").trim_start_matches('<').trim_end_matches('>').to_string();
+            if content_id.is_empty() || content_id.chars().any(|c| c.is_whitespace() || c.is_control() || c == '<' || c == '>') {
+                return Err(AppError::BadRequest(format!("Invalid content_id for attachment '{}'", att.filename)));
+            }
+            att.content_id = Some(content_id);
+        }
+        Ok(att)
+    }).collect()).transpose()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReplyMode {
+    Reply,
+    ReplyAll,
+    Forward,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Draft {
+    pub id: String,
+    pub message_id: Option<String>,
+    pub thread_id: Option<String>,
+    pub subject: Option<String>,
+    pub to: Option<Vec<String>>,
+    pub snippet: Option<String>,
+    pub date: Option<String>,
+}
+
+// Canonical system label ids, accepted and returned by every provider
+pub const SYSTEM_LABELS: [&str; 7] = ["INBOX", "SENT", "DRAFT", "TRASH", "SPAM", "ARCHIVE", "STARRED"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Label {
     pub id: String,
     pub name: String,
     pub label_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<LabelColor>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unread_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_count: Option<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<Label>,
+}
+
+impl Label {
+    // Placeholder for a canonical id the provider has no real folder for (Gmail ARCHIVE, Outlook STARRED)
+    pub fn virtual_system(id: &str, name: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            label_type: Some("system".to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LabelColor {
+    pub text_color: String,
+    pub background_color: String,
+}
+
+// Body of POST /api/labels and PATCH /api/labels/:id; absent fields are left unchanged on PATCH
+#[derive(Deserialize, Debug, Default)]
+pub struct LabelRequest {
+    pub name: Option<String>,
+    pub parent_id: Option<String>, // Gmail: prefixes the parent's name, Outlook: childFolders
+    pub color: Option<LabelColor>, // Gmail only, must be from Gmail's palette
 }
 
 #[derive(Deserialize, Debug)]
@@ -75,13 +587,61 @@ pub struct BatchModifyRequest {
     pub remove_label_ids: Option<Vec<String>>,
 }
 
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchItemStatus {
+    Succeeded,
+    Failed,
+    Skipped,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BatchItemResult {
+    pub id: String,
+    pub status: BatchItemStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl BatchItemResult {
+    pub fn succeeded(id: String) -> Self {
+        Self { id, status: BatchItemStatus::Succeeded, error: None }
+    }
+
+    pub fn failed(id: String, reason: impl Into<String>) -> Self {
+        Self { id, status: BatchItemStatus::Failed, error: Some(reason.into()) }
+    }
+
+    pub fn skipped(id: String, reason: impl Into<String>) -> Self {
+        Self { id, status: BatchItemStatus::Skipped, error: Some(reason.into()) }
+    }
+}
+
 #[async_trait]
 pub trait EmailProvider: Send + Sync {
-    async fn list_messages(&self, token: &str, params: ListParams) -> Result<serde_json::Value, AppError>;
-    async fn get_message(&self, token: &str, id: &str) -> Result<CleanMessage, AppError>;
+    async fn list_messages(&self, token: &str, params: ListParams) -> Result<MessageList, AppError>;
+    async fn get_message(&self, token: &str, id: &str, format: MessageFormat) -> Result<CleanMessage, AppError>;
+    async fn get_thread(&self, token: &str, thread_id: &str) -> Result<Thread, AppError>;
+    async fn get_attachment(&self, token: &str, message_id: &str, attachment_id: &str) -> Result<AttachmentContent, AppError>;
     async fn send_message(&self, token: &str, req: SendMessageRequest) -> Result<serde_json::Value, AppError>;
-    async fn list_labels(&self, token: &str) -> Result<Vec<Label>, AppError>;
-    async fn batch_modify_labels(&self, token: &str, req: BatchModifyRequest) -> Result<(), AppError>;
+    async fn reply(&self, token: &str, message_id: &str, mode: ReplyMode, req: ReplyRequest) -> Result<serde_json::Value, AppError>;
+    async fn list_drafts(&self, token: &str, max_results: Option<u32>) -> Result<Vec<Draft>, AppError>;
+    async fn create_draft(&self, token: &str, req: SendMessageRequest) -> Result<Draft, AppError>;
+    async fn update_draft(&self, token: &str, draft_id: &str, req: SendMessageRequest) -> Result<Draft, AppError>;
+    async fn send_draft(&self, token: &str, draft_id: &str) -> Result<serde_json::Value, AppError>;
+    async fn mark_read(&self, token: &str, id: &str, read: bool) -> Result<(), AppError>;
+    async fn set_starred(&self, token: &str, id: &str, starred: bool) -> Result<(), AppError>;
+    async fn archive(&self, token: &str, id: &str) -> Result<(), AppError>;
+    async fn trash(&self, token: &str, id: &str) -> Result<(), AppError>;
+    async fn restore(&self, token: &str, id: &str) -> Result<(), AppError>;
+    async fn delete_message(&self, token: &str, id: &str) -> Result<(), AppError>; // Permanent, bypasses trash
+    async fn get_raw_message(&self, token: &str, id: &str) -> Result<Vec<u8>, AppError>; // RFC 822 source
+    async fn import_message(&self, token: &str, raw: Vec<u8>, label_id: &str) -> Result<serde_json::Value, AppError>;
+    async fn list_labels(&self, token: &str, with_counts: bool) -> Result<Vec<Label>, AppError>; // Counts cost a request per label on Gmail
+    async fn create_label(&self, token: &str, req: LabelRequest) -> Result<Label, AppError>;
+    async fn update_label(&self, token: &str, label_id: &str, req: LabelRequest) -> Result<Label, AppError>;
+    async fn delete_label(&self, token: &str, label_id: &str) -> Result<(), AppError>;
+    async fn batch_modify_labels(&self, token: &str, req: BatchModifyRequest) -> Result<Vec<BatchItemResult>, AppError>;
     async fn get_profile(&self, token: &str) -> Result<UserProfile, AppError>;
 }
 
@@ -94,3 +654,265 @@ pub struct ListParams {
     pub page_number: Option<u32>,
     pub collapse_threads: Option<bool>,
 }
+
+/// Markers that introduce the quoted history of a reply in the common clients
+/// (Gmail, Apple Mail, Outlook desktop/web).
+const QUOTE_MARKERS: &[&str] = &[
+    "-----Original Message-----",
+    "________________________________",
+    "---------- Forwarded message",
+];
+
+/// Returns the part of a plain-text body written by the sender, i.e. everything
+/// before the first quoted-history marker. `None` if the body has no quoted part.
+pub fn strip_quoted_text(body: &str) -> Option<String> {
+    let mut offset = 0;
+    let mut cut = None;
+
+    for line in body.split_inclusive('\n') {
+        let trimmed = line.trim();
+        let is_marker = trimmed.starts_with('>')
+            || QUOTE_MARKERS.iter().any(|m| trimmed.starts_with(m))
+            // "On Mon, 1 Jan 2024 at 10:00, John <john@example.com> wrote:"
+            || (trimmed.starts_with("On ") && trimmed.ends_with("wrote:"))
+            // Outlook header block: "From: ..." directly followed by "Sent: ..."
+            || (trimmed.starts_with("From:") && body[offset + line.len()..].trim_start().starts_with("Sent:"));
+
+        if is_marker {
+            cut = Some(offset);
+            break;
+        }
+        offset += line.len();
+    }
+
+    cut.map(|idx| body[..idx].trim_end().to_string())
+}
+
+/// Fills `reply_text` for every message of a thread after the first one, so the
+/// client can render the conversation without repeating the quoted history.
+pub fn dedup_quoted_text(messages: &mut [CleanMessage]) {
+    for msg in messages.iter_mut().skip(1) {
+        msg.reply_text = msg.body_text.as_deref().and_then(strip_quoted_text);
+    }
+}
+
+// Drops empty and repeated ids before a batch goes upstream, reporting them as skipped
+pub fn partition_batch_ids(ids: Vec<String>) -> (Vec<String>, Vec<BatchItemResult>) {
+    let mut seen = std::collections::HashSet::new();
+    let mut unique = Vec::new();
+    let mut skipped = Vec::new();
+
+    for id in ids {
+        if id.trim().is_empty() {
+            skipped.push(BatchItemResult::skipped(id, "Empty message id"));
+        } else if !seen.insert(id.clone()) {
+            skipped.push(BatchItemResult::skipped(id, "Duplicate message id"));
+        } else {
+            unique.push(id);
+        }
+    }
+
+    (unique, skipped)
+}
+
+// Nests a flat label list by parent_id; labels whose parent is missing become roots
+pub fn build_label_tree(labels: Vec<Label>) -> Vec<Label> {
+    let ids: std::collections::HashSet<String> = labels.iter().map(|l| l.id.clone()).collect();
+    let mut children: std::collections::HashMap<String, Vec<Label>> = std::collections::HashMap::new();
+    let mut roots = Vec::new();
+
+    for label in labels {
+        match label.parent_id.clone().filter(|p| ids.contains(p) && *p != label.id) {
+            Some(parent_id) => children.entry(parent_id).or_default().push(label),
+            None => roots.push(label),
+        }
+    }
+
+    fn attach(label: &mut Label, children: &mut std::collections::HashMap<String, Vec<Label>>) {
+        if let Some(mut kids) = children.remove(&label.id) {
+            for kid in &mut kids {
+                attach(kid, children);
+            }
+            label.children = kids;
+        }
+    }
+
+    for root in &mut roots {
+        attach(root, &mut children);
+    }
+
+    roots
+}
+
+/// A provider date (RFC 3339 or an RFC 2822 `Date` header) as RFC 3339 UTC
+pub fn normalize_date(raw: &str) -> Option<String> {
+    let raw = raw.trim();
+    // Headers often carry a trailing comment such as "(UTC)" or "(Pacific Standard Time)"
+    let without_comment = match raw.rfind('(') {
+        Some(i) if raw.ends_with(')') => raw[..i].trim(),
+        _ => raw,
+    };
+
+    chrono::DateTime::parse_from_rfc3339(raw)
+        .or_else(|_| chrono::DateTime::parse_from_rfc2822(without_comment))
+        .ok()
+        .map(|d| d.with_timezone(&chrono::Utc).to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+}
+
+/// Milliseconds since the epoch (Gmail internalDate) as RFC 3339 UTC
+pub fn date_from_millis(millis: i64) -> Option<String> {
+    chrono::DateTime::from_timestamp_millis(millis)
+        .map(|d| d.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(json: serde_json::Value) -> SendMessageRequest {
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn pdf(filename: &str) -> serde_json::Value {
+        serde_json::json!({ "filename": filename, "content": [37, 80, 68, 70], "mime_type": "application/pdf" })
+    }
+
+    // POST /api/messages/send and POST/PUT /api/drafts share SendMessageRequest::sanitize_headers
+    #[test]
+    fn send_folds_line_breaks_in_subject_and_filenames() {
+        let req = message(serde_json::json!({
+            "to": ["anna@example.com"],
+            "subject": "Quote\r\nBcc: victim@example.com",
+            "body": "<p>Hi</p>",
+            "attachments": [pdf("offer.pdf\r\nContent-Type: text/html")],
+        })).sanitize_headers().unwrap();
+
+        assert_eq!(req.subject, "Quote Bcc: victim@example.com");
+        assert_eq!(req.attachments.unwrap()[0].filename, "offer.pdf Content-Type: text/html");
+    }
+
+    #[test]
+    fn send_rejects_line_breaks_in_recipients() {
+        for field in ["to", "cc", "bcc", "reply_to"] {
+            let mut json = serde_json::json!({ "to": ["anna@example.com"], "subject": "Hi", "body": "" });
+            json[field] = serde_json::json!(["bob@example.com\r\nBcc: victim@example.com"]);
+
+            match message(json).sanitize_headers() {
+                Err(AppError::InvalidAddresses(errors)) => assert_eq!(errors[0].field, field),
+                other => panic!("{}: expected InvalidAddresses, got {:?}", field, other.map(|r| r.to)),
+            }
+        }
+    }
+
+    #[test]
+    fn draft_rejects_line_breaks_in_threading_headers() {
+        for field in ["in_reply_to", "references"] {
+            let mut json = serde_json::json!({ "to": ["anna@example.com"], "subject": "Re: Hi", "body": "" });
+            json[field] = serde_json::json!("<a@example.com>\r\nBcc: victim@example.com");
+            assert!(matches!(message(json).sanitize_headers(), Err(AppError::BadRequest(_))), "{}", field);
+        }
+
+        let req = message(serde_json::json!({
+            "to": ["anna@example.com"], "subject": "Re: Hi", "body": "",
+            "in_reply_to": "<a@example.com>", "references": "<z@example.com> <a@example.com>",
+        })).sanitize_headers().unwrap();
+        assert_eq!(req.references.as_deref(), Some("<z@example.com> <a@example.com>"));
+    }
+
+    #[test]
+    fn send_rejects_other_control_characters() {
+        let subject = message(serde_json::json!({ "to": ["anna@example.com"], "subject": "Hi\u{0}there", "body": "" }));
+        assert!(matches!(subject.sanitize_headers(), Err(AppError::BadRequest(_))));
+
+        let mut attachment = pdf("offer.pdf");
+        attachment["mime_type"] = serde_json::json!("application/pdf\r\nX-Evil: 1");
+        let mime_type = message(serde_json::json!({ "to": ["anna@example.com"], "subject": "Hi", "body": "", "attachments": [attachment] }));
+        assert!(matches!(mime_type.sanitize_headers(), Err(AppError::BadRequest(_))));
+    }
+
+    #[test]
+    fn sending_requires_a_recipient() {
+        let empty = message(serde_json::json!({ "to": [" ", ""], "cc": [], "subject": "Hi", "body": "" })).sanitize_headers().unwrap();
+        assert!(empty.to.is_empty());
+        assert!(matches!(empty.require_recipients(), Err(AppError::BadRequest(_))));
+
+        let bcc_only = message(serde_json::json!({ "to": [], "bcc": ["anna@example.com"], "subject": "Hi", "body": "" }));
+        assert!(bcc_only.sanitize_headers().and_then(SendMessageRequest::require_recipients).is_ok());
+    }
+
+    #[test]
+    fn custom_headers_are_checked() {
+        let with_header = |name: &str, importance: Option<&str>| message(serde_json::json!({
+            "to": ["anna@example.com"], "subject": "Hi", "body": "",
+            "headers": { name: "1" },
+            "importance": importance,
+        })).sanitize_headers();
+
+        assert!(with_header("X-Quote-Id", None).is_ok());
+        assert!(with_header("X-Priority", None).is_ok());
+        for name in ["Bcc", "X-", "€x", "x€-a", "X-Bad Name"] {
+            assert!(matches!(with_header(name, None), Err(AppError::BadRequest(_))), "{}", name);
+        }
+        assert!(matches!(with_header("x-priority", Some("high")), Err(AppError::BadRequest(_))));
+        assert!(with_header("X-Quote-Id", Some("high")).is_ok());
+    }
+
+    #[test]
+    fn only_public_addresses_are_fetched() {
+        for ip in ["8.8.8.8", "1.1.1.1", "2606:4700:4700::1111", "::ffff:8.8.8.8", "64:ff9b::808:808"] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "100.100.100.200",
+            "0.0.0.0", "255.255.255.255", "224.0.0.1", "192.0.0.192", "198.18.0.1",
+            "::1", "::", "fe80::1", "fd00:ec2::254", "fc00::1", "::ffff:127.0.0.1", "::ffff:169.254.169.254",
+            "64:ff9b::7f00:1",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn private_and_non_http_urls_are_not_requested() {
+        for url in [
+            "http://127.0.0.1/logo.png",
+            "http://localhost:8080/logo.png",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://[::1]/logo.png",
+            "http://[::ffff:10.0.0.1]/logo.png",
+            "file:///etc/passwd",
+        ] {
+            assert!(fetch_public_url(url).await.is_err(), "{}", url);
+        }
+    }
+
+    #[tokio::test]
+    async fn private_images_keep_their_url() {
+        let req = message(serde_json::json!({
+            "to": ["anna@example.com"], "subject": "Hi", "inline_images": true,
+            "body": r#"<img src="http://127.0.0.1/logo.png"><img src='http://169.254.169.254/x'>"#,
+        })).inline_remote_images().await.unwrap();
+
+        assert_eq!(req.body, r#"<img src="http://127.0.0.1/logo.png"><img src='http://169.254.169.254/x'>"#);
+        assert!(req.attachments.unwrap_or_default().is_empty());
+    }
+
+    #[test]
+    fn reply_sanitizes_recipients_and_filenames() {
+        let reply: ReplyRequest = serde_json::from_value(serde_json::json!({
+            "body": "<p>Thanks</p>",
+            "to": ["Anna <anna@example.com>"],
+            "attachments": [pdf("notes\n.pdf")],
+        })).unwrap();
+        let reply = reply.sanitize_headers().unwrap();
+        assert_eq!(reply.to.unwrap(), vec!["\"Anna\" <anna@example.com>"]);
+        assert_eq!(reply.attachments.unwrap()[0].filename, "notes .pdf");
+
+        for field in ["to", "cc"] {
+            let mut json = serde_json::json!({ "body": "" });
+            json[field] = serde_json::json!(["anna@example.com\nBcc: victim@example.com"]);
+            let reply: ReplyRequest = serde_json::from_value(json).unwrap();
+            assert!(matches!(reply.sanitize_headers(), Err(AppError::InvalidAddresses(_))), "{}", field);
+        }
+    }
+}
@@ -0,0 +1,325 @@
+use chrono::NaiveDate;
+
+use crate::error::AppError;
+
+// Provider-neutral search syntax accepted in `q`:
+//   free text, "quoted phrase", from:, to:, subject:, has:attachment,
+//   is:unread / is:read / is:starred, before:/after: (YYYY-MM-DD or YYYY/MM/DD), label:
+// Any other "word:value" (a URL, a time) is searched as free text.
+// Parsed once here and compiled into Gmail `q` or Graph `$search`/`$filter`.
+
+/// Gmail has no archive label, archived mail is everything outside the system folders
+pub const GMAIL_ARCHIVE_QUERY: &str = "-in:inbox -in:sent -in:drafts -in:trash -in:spam";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    Text(String),
+    From(String),
+    To(String),
+    Subject(String),
+    HasAttachment,
+    IsUnread,
+    IsRead,
+    IsStarred,
+    Before(NaiveDate),
+    After(NaiveDate),
+    Label(String),
+}
+
+#[derive(Debug, Default)]
+pub struct SearchQuery {
+    pub terms: Vec<Term>,
+}
+
+/// Graph side of a query: `$search` (KQL) and `$filter` clauses cannot be combined on messages
+#[derive(Debug, Default)]
+pub struct GraphQuery {
+    pub search: Option<String>,
+    pub filter: Vec<String>,
+}
+
+impl SearchQuery {
+    pub fn parse(input: &str) -> Result<Self, AppError> {
+        let mut terms = Vec::new();
+
+        for (key, value) in tokenize(input)? {
+            let Some(key) = key else {
+                terms.push(Term::Text(value));
+                continue;
+            };
+
+            let term = match key.to_lowercase().as_str() {
+                "from" | "to" | "subject" | "label" | "before" | "after" | "has" | "is" if value.is_empty() => {
+                    return Err(AppError::BadRequest(format!("Search operator '{}:' needs a value", key)));
+                }
+                "from" => Term::From(value),
+                "to" => Term::To(value),
+                "subject" => Term::Subject(value),
+                "label" => Term::Label(value),
+                "before" => Term::Before(parse_date(&key, &value)?),
+                "after" => Term::After(parse_date(&key, &value)?),
+                "has" => match value.to_lowercase().as_str() {
+                    "attachment" | "attachments" => Term::HasAttachment,
+                    _ => return Err(AppError::BadRequest(format!("Unsupported search value 'has:{}'", value))),
+                },
+                "is" => match value.to_lowercase().as_str() {
+                    "unread" => Term::IsUnread,
+                    "read" => Term::IsRead,
+                    "starred" | "flagged" => Term::IsStarred,
+                    _ => return Err(AppError::BadRequest(format!("Unsupported search value 'is:{}'", value))),
+                },
+                _ => Term::Text(format!("{}:{}", key, value)),
+            };
+            terms.push(term);
+        }
+
+        Ok(Self { terms })
+    }
+
+    pub fn labels(&self) -> Vec<&str> {
+        self.terms.iter().filter_map(|t| match t {
+            Term::Label(l) => Some(l.as_str()),
+            _ => None,
+        }).collect()
+    }
+
+    pub fn to_gmail(&self) -> String {
+        self.terms.iter().map(|term| match term {
+            Term::Text(t) => quote(t),
+            Term::From(v) => format!("from:{}", quote(v)),
+            Term::To(v) => format!("to:{}", quote(v)),
+            Term::Subject(v) => format!("subject:{}", quote(v)),
+            Term::HasAttachment => "has:attachment".to_string(),
+            Term::IsUnread => "is:unread".to_string(),
+            Term::IsRead => "is:read".to_string(),
+            Term::IsStarred => "is:starred".to_string(),
+            Term::Before(d) => format!("before:{}", d.format("%Y/%m/%d")),
+            Term::After(d) => format!("after:{}", d.format("%Y/%m/%d")),
+            Term::Label(l) => gmail_label_term(l),
+        }).collect::<Vec<_>>().join(" ")
+    }
+
+    /// Everything except `label:`, which the Outlook provider resolves to a folder itself
+    pub fn to_graph(&self) -> Result<GraphQuery, AppError> {
+        let needs_search = self.terms.iter().any(|t| matches!(t, Term::Text(_) | Term::From(_) | Term::To(_) | Term::Subject(_)));
+
+        if needs_search {
+            let mut kql = Vec::new();
+            for term in &self.terms {
+                match term {
+                    Term::Text(t) => kql.push(kql_quote(t)),
+                    Term::From(v) => kql.push(format!("from:{}", kql_quote(v))),
+                    Term::To(v) => kql.push(format!("to:{}", kql_quote(v))),
+                    Term::Subject(v) => kql.push(format!("subject:{}", kql_quote(v))),
+                    Term::HasAttachment => kql.push("hasAttachments:true".to_string()),
+                    Term::Before(d) => kql.push(format!("received<{}", d.format("%Y-%m-%d"))),
+                    Term::After(d) => kql.push(format!("received>={}", d.format("%Y-%m-%d"))),
+                    Term::IsUnread | Term::IsRead | Term::IsStarred => {
+                        return Err(AppError::BadRequest(
+                            "is: cannot be combined with text, from:, to: or subject: on Outlook".to_string(),
+                        ));
+                    }
+                    Term::Label(_) => {}
+                }
+            }
+            return Ok(GraphQuery { search: Some(kql.join(" ")), filter: Vec::new() });
+        }
+
+        // Date clauses go first: Graph wants the $orderby property to lead the $filter
+        let mut dates = Vec::new();
+        let mut filter = Vec::new();
+        for term in &self.terms {
+            match term {
+                Term::After(d) => dates.push(format!("receivedDateTime ge {}T00:00:00Z", d.format("%Y-%m-%d"))),
+                Term::Before(d) => dates.push(format!("receivedDateTime lt {}T00:00:00Z", d.format("%Y-%m-%d"))),
+                Term::HasAttachment => filter.push("hasAttachments eq true".to_string()),
+                Term::IsUnread => filter.push("isRead eq false".to_string()),
+                Term::IsRead => filter.push("isRead eq true".to_string()),
+                Term::IsStarred => filter.push("flag/flagStatus eq 'flagged'".to_string()),
+                _ => {}
+            }
+        }
+        dates.append(&mut filter);
+
+        Ok(GraphQuery { search: None, filter: dates })
+    }
+}
+
+// Splits on whitespace outside double quotes into (operator, value) pairs.
+// A ':' only introduces an operator when it follows a bare word, so "a:b" in quotes stays text.
+fn tokenize(input: &str) -> Result<Vec<(Option<String>, String)>, AppError> {
+    let mut tokens = Vec::new();
+    let mut key: Option<String> = None;
+    let mut buf = String::new();
+    let mut in_quotes = false;
+    let mut quoted = false;
+
+    let mut flush = |key: &mut Option<String>, buf: &mut String, quoted: &mut bool| {
+        if key.is_some() || !buf.is_empty() {
+            tokens.push((key.take(), std::mem::take(buf)));
+        }
+        *quoted = false;
+    };
+
+    for c in input.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                quoted = true;
+            }
+            ':' if !in_quotes && !quoted && key.is_none() && !buf.is_empty() && buf.chars().all(|c| c.is_ascii_alphabetic()) => {
+                key = Some(std::mem::take(&mut buf));
+            }
+            c if c.is_whitespace() && !in_quotes => flush(&mut key, &mut buf, &mut quoted),
+            c => buf.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err(AppError::BadRequest("Unterminated quote in search query".to_string()));
+    }
+    flush(&mut key, &mut buf, &mut quoted);
+
+    Ok(tokens)
+}
+
+fn parse_date(key: &str, value: &str) -> Result<NaiveDate, AppError> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(value, "%Y/%m/%d"))
+        .map_err(|_| AppError::BadRequest(format!("Invalid date in '{}:{}', expected YYYY-MM-DD", key, value)))
+}
+
+// Gmail phrases are double-quoted; Gmail has no escape for inner quotes, so they are dropped
+fn quote(value: &str) -> String {
+    if value.chars().any(|c| c.is_whitespace() || "(){}\":".contains(c)) {
+        format!("\"{}\"", value.replace('"', ""))
+    } else {
+        value.to_string()
+    }
+}
+
+// KQL phrases sit inside the outer $search="..." quotes, so their own quotes are escaped.
+// A ':' outside a phrase would start a property restriction.
+fn kql_quote(value: &str) -> String {
+    let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+    if value.chars().any(|c| c.is_whitespace() || c == ':') {
+        format!("\\\"{}\\\"", escaped)
+    } else {
+        escaped
+    }
+}
+
+// Canonical system ids map to Gmail's own operators, user labels use hyphens for spaces and nesting
+fn gmail_label_term(label: &str) -> String {
+    match label.to_uppercase().as_str() {
+        "INBOX" => "in:inbox".to_string(),
+        "SENT" => "in:sent".to_string(),
+        "DRAFT" => "in:drafts".to_string(),
+        "TRASH" => "in:trash".to_string(),
+        "SPAM" => "in:spam".to_string(),
+        "STARRED" => "is:starred".to_string(),
+        "ARCHIVE" => GMAIL_ARCHIVE_QUERY.to_string(),
+        _ => format!("label:{}", label.replace(['/', ' '], "-")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn parses_operators_and_free_text() {
+        let query = SearchQuery::parse(r#"invoice "due soon" from:alice@example.com Subject:Report has:attachment is:unread"#).unwrap();
+        assert_eq!(query.terms, vec![
+            Term::Text("invoice".to_string()),
+            Term::Text("due soon".to_string()),
+            Term::From("alice@example.com".to_string()),
+            Term::Subject("Report".to_string()),
+            Term::HasAttachment,
+            Term::IsUnread,
+        ]);
+    }
+
+    #[test]
+    fn parses_both_date_formats() {
+        let query = SearchQuery::parse("after:2024-01-02 before:2024/02/03").unwrap();
+        assert_eq!(query.terms, vec![Term::After(date(2024, 1, 2)), Term::Before(date(2024, 2, 3))]);
+        assert!(SearchQuery::parse("before:yesterday").is_err());
+    }
+
+    #[test]
+    fn quoted_operator_values_keep_spaces() {
+        let query = SearchQuery::parse(r#"subject:"quarterly report" label:"My Folder""#).unwrap();
+        assert_eq!(query.terms, vec![
+            Term::Subject("quarterly report".to_string()),
+            Term::Label("My Folder".to_string()),
+        ]);
+        assert_eq!(query.labels(), vec!["My Folder"]);
+    }
+
+    #[test]
+    fn unknown_operators_are_free_text() {
+        let query = SearchQuery::parse("http://example.com/a Note:x 10:30").unwrap();
+        assert_eq!(query.terms, vec![
+            Term::Text("http://example.com/a".to_string()),
+            Term::Text("Note:x".to_string()),
+            Term::Text("10:30".to_string()),
+        ]);
+        assert_eq!(query.to_gmail(), r#""http://example.com/a" "Note:x" "10:30""#);
+    }
+
+    #[test]
+    fn quoted_colon_is_not_an_operator() {
+        let query = SearchQuery::parse(r#""from:me""#).unwrap();
+        assert_eq!(query.terms, vec![Term::Text("from:me".to_string())]);
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        assert!(SearchQuery::parse("from:").is_err());
+        assert!(SearchQuery::parse("has:pdf").is_err());
+        assert!(SearchQuery::parse("is:important").is_err());
+        assert!(SearchQuery::parse(r#"subject:"open"#).is_err());
+    }
+
+    #[test]
+    fn compiles_to_gmail() {
+        let query = SearchQuery::parse(r#"from:"Alice Smith" after:2024-01-02 label:inbox label:Customers/Acme is:starred"#).unwrap();
+        assert_eq!(
+            query.to_gmail(),
+            r#"from:"Alice Smith" after:2024/01/02 in:inbox label:Customers-Acme is:starred"#
+        );
+        assert_eq!(SearchQuery::parse("label:archive").unwrap().to_gmail(), GMAIL_ARCHIVE_QUERY);
+    }
+
+    #[test]
+    fn compiles_text_terms_to_graph_search() {
+        let query = SearchQuery::parse(r#"from:bob "two words" http://x has:attachment after:2024-01-02 label:INBOX"#).unwrap();
+        let graph = query.to_graph().unwrap();
+        assert_eq!(
+            graph.search.as_deref(),
+            Some(r#"from:bob \"two words\" \"http://x\" hasAttachments:true received>=2024-01-02"#)
+        );
+        assert!(graph.filter.is_empty());
+    }
+
+    #[test]
+    fn compiles_flags_to_graph_filter_with_dates_first() {
+        let graph = SearchQuery::parse("is:unread before:2024-03-01 is:starred").unwrap().to_graph().unwrap();
+        assert_eq!(graph.search, None);
+        assert_eq!(graph.filter, vec![
+            "receivedDateTime lt 2024-03-01T00:00:00Z".to_string(),
+            "isRead eq false".to_string(),
+            "flag/flagStatus eq 'flagged'".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn graph_rejects_flags_mixed_with_search() {
+        assert!(SearchQuery::parse("invoice is:unread").unwrap().to_graph().is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use axum::{
-    routing::{get, post},
+    extract::DefaultBodyLimit,
+    routing::{get, patch, post, put},
     Router,
 };
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
@@ -26,6 +27,7 @@ async fn main() {
 
     // Load configuration (Fix Point 5 & 8)
     let config = config::Config::load().expect("Failed to load configuration");
+    handlers::pagination::init(&config.signing_key);
     let client = reqwest::Client::builder()
         .timeout(std::time::Duration::from_secs(30))
         .build()
@@ -40,9 +42,32 @@ async fn main() {
     let app = Router::new()
         .route("/health", get(handlers::health::check))
         .route("/api/messages", get(handlers::api::list_messages))
-        .route("/api/messages/:id", get(handlers::api::get_message))
+        .route("/api/messages/:id", get(handlers::api::get_message).delete(handlers::api::delete_message))
         .route("/api/messages/send", post(handlers::api::send_message))
-        .route("/api/labels", get(handlers::api::list_labels))
+        .route(
+            "/api/messages/import",
+            post(handlers::api::import_message).layer(DefaultBodyLimit::max(handlers::api::MAX_IMPORT_SIZE)),
+        )
+        .route("/api/messages/:id/raw", get(handlers::api::get_raw_message))
+        .route("/api/messages/:id/attachments/:attachment_id", get(handlers::api::get_attachment))
+        .route("/api/messages/:id/attachments/:attachment_id/link", post(handlers::api::create_attachment_link))
+        .route("/api/messages/:id/reply", post(handlers::api::reply_message))
+        .route("/api/messages/:id/reply-all", post(handlers::api::reply_all_message))
+        .route("/api/messages/:id/forward", post(handlers::api::forward_message))
+        .route("/api/messages/:id/read", post(handlers::api::mark_read))
+        .route("/api/messages/:id/unread", post(handlers::api::mark_unread))
+        .route("/api/messages/:id/star", post(handlers::api::star_message))
+        .route("/api/messages/:id/unstar", post(handlers::api::unstar_message))
+        .route("/api/messages/:id/archive", post(handlers::api::archive_message))
+        .route("/api/messages/:id/trash", post(handlers::api::trash_message))
+        .route("/api/messages/:id/restore", post(handlers::api::restore_message))
+        .route("/api/threads/:id", get(handlers::api::get_thread))
+        .route("/api/drafts", get(handlers::api::list_drafts).post(handlers::api::create_draft))
+        .route("/api/drafts/:id", put(handlers::api::update_draft))
+        .route("/api/drafts/:id/send", post(handlers::api::send_draft))
+        .route("/api/labels", get(handlers::api::list_labels).post(handlers::api::create_label))
+        .route("/api/labels/tree", get(handlers::api::get_label_tree))
+        .route("/api/labels/:id", patch(handlers::api::update_label).delete(handlers::api::delete_label))
         .route("/api/labels/batch-modify", post(handlers::api::batch_modify_labels))
         .route("/api/profile", get(handlers::api::get_profile))
         .route("/api/quote/preview", post(handlers::api::preview_quote))
@@ -52,12 +77,14 @@ async fn main() {
         .route_layer(axum::middleware::from_fn_with_state(state.clone(), middleware::auth::verify_api_key))
         // Explicitly serve embed.js
         .route("/embed.js", get(handlers::api::get_embed_js))
+        // Signed attachment links carry their own credentials (HMAC + expiry)
+        .route("/api/attachments/signed", get(handlers::api::get_signed_attachment))
         .layer(TraceLayer::new_for_http())
         .layer(tower_http::compression::CompressionLayer::new())
         // Fix Point 4: More restrictive CORS for production
         .layer({
             let mut cors = CorsLayer::new()
-                .allow_methods([axum::http::Method::GET, axum::http::Method::POST])
+                .allow_methods([axum::http::Method::GET, axum::http::Method::POST, axum::http::Method::PUT, axum::http::Method::PATCH, axum::http::Method::DELETE])
                 .allow_headers([axum::http::header::CONTENT_TYPE, axum::http::HeaderName::from_static("x-api-key"), axum::http::header::AUTHORIZATION]);
             
             if state.config.allowed_origins.contains(&"*".to_string()) {
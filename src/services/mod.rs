@@ -1 +1,2 @@
 pub mod bubble;
+pub mod signing;
@@ -0,0 +1,72 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Nonce,
+};
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const NONCE_LEN: usize = 12;
+
+/// Signs and seals values that are handed out to clients (signed links),
+/// so they can be verified by any instance sharing the same server key.
+pub struct Signer {
+    mac_key: [u8; 32],
+    cipher: Aes256Gcm,
+}
+
+impl Signer {
+    pub fn new(secret: &str) -> Self {
+        // Separate sub-keys so a signature can never be replayed as ciphertext and vice versa
+        let mac_key: [u8; 32] = Sha256::digest(format!("sign:{}", secret)).into();
+        let enc_key: [u8; 32] = Sha256::digest(format!("seal:{}", secret)).into();
+
+        Self {
+            mac_key,
+            cipher: Aes256Gcm::new(&enc_key.into()),
+        }
+    }
+
+    fn mac(&self, data: &str) -> HmacSha256 {
+        let mut mac = <HmacSha256 as Mac>::new_from_slice(&self.mac_key).expect("HMAC accepts any key length");
+        mac.update(data.as_bytes());
+        mac
+    }
+
+    /// HMAC-SHA256 of `data`, base64url encoded.
+    pub fn sign(&self, data: &str) -> String {
+        URL_SAFE_NO_PAD.encode(self.mac(data).finalize().into_bytes())
+    }
+
+    /// Constant-time check of a signature produced by `sign`.
+    pub fn verify(&self, data: &str, signature: &str) -> bool {
+        let Ok(expected) = URL_SAFE_NO_PAD.decode(signature) else {
+            return false;
+        };
+        self.mac(data).verify_slice(&expected).is_ok()
+    }
+
+    /// Encrypts `plaintext` with AES-256-GCM. Output is base64url(nonce || ciphertext).
+    pub fn seal(&self, plaintext: &[u8]) -> String {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self.cipher
+            .encrypt(&nonce, plaintext)
+            .expect("AES-GCM encryption does not fail for in-memory buffers");
+
+        let mut out = nonce.to_vec();
+        out.extend_from_slice(&ciphertext);
+        URL_SAFE_NO_PAD.encode(out)
+    }
+
+    /// Reverses `seal`. Returns `None` if the value was tampered with or sealed under another key.
+    pub fn open(&self, sealed: &str) -> Option<Vec<u8>> {
+        let raw = URL_SAFE_NO_PAD.decode(sealed).ok()?;
+        if raw.len() <= NONCE_LEN {
+            return None;
+        }
+        let (nonce, ciphertext) = raw.split_at(NONCE_LEN);
+        self.cipher.decrypt(Nonce::from_slice(nonce), ciphertext).ok()
+    }
+}